@@ -1,5 +1,286 @@
-#[derive(Debug, Clone, Copy)]
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Time to wait for progress on a chunk before the first retransmission. Every retry doubles
+/// it, up to `MAX_REQUEST_TIMEOUT`.
+const INITIAL_REQUEST_TIMEOUT: Duration = Duration::from_millis(250);
+const MAX_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default)]
 pub struct ChunkControlData {
     pub received: bool,
-    pub sent_get: bool,
+    /// Peer that the chunk was last requested from.
+    pub source: Option<SocketAddr>,
+    /// Every peer the current copy of the chunk was requested from. Fragments from other
+    /// peers are not accepted.
+    pub requested_from: Vec<SocketAddr>,
+    /// Every peer that advertised the chunk in a ChunkInfo message.
+    pub providers: Vec<SocketAddr>,
+    pub first_advertised_at: Option<Instant>,
+    pub retries: u32,
+    pub fragments: Vec<Option<Vec<u8>>>,
+    /// Time of the last request sent or fragment received for this chunk.
+    pub last_activity: Option<Instant>,
+    /// Peers that sent at least one of the fragments held in `fragments`.
+    pub contributors: Vec<SocketAddr>,
+    /// Peers that sent fragments of a copy of this chunk that failed verification.
+    pub rejected: Vec<SocketAddr>,
+}
+
+impl ChunkControlData {
+    pub fn sent_get(&self) -> bool {
+        self.source.is_some()
+    }
+
+    pub fn add_provider(&mut self, provider: SocketAddr) {
+        if self.first_advertised_at.is_none() {
+            self.first_advertised_at = Some(Instant::now());
+        }
+
+        if !self.providers.contains(&provider) {
+            self.providers.push(provider);
+        }
+    }
+
+    /// Providers that did not send corrupt data for this chunk.
+    pub fn trusted_providers(&self) -> Vec<SocketAddr> {
+        self.providers
+            .iter()
+            .copied()
+            .filter(|provider| !self.rejected.contains(provider))
+            .collect()
+    }
+
+    pub fn mark_requested(&mut self, provider: SocketAddr) {
+        self.source = Some(provider);
+        if !self.requested_from.contains(&provider) {
+            self.requested_from.push(provider);
+        }
+        self.last_activity = Some(Instant::now());
+    }
+
+    /// Whether a request for this chunk is outstanding and has made no progress within the
+    /// current backoff interval.
+    pub fn is_stalled(&self) -> bool {
+        if self.received {
+            return false;
+        }
+
+        match self.last_activity {
+            Some(last_activity) => last_activity.elapsed() > self.request_timeout(),
+            None => false,
+        }
+    }
+
+    fn request_timeout(&self) -> Duration {
+        let timeout = INITIAL_REQUEST_TIMEOUT * 2u32.saturating_pow(self.retries.min(8));
+        timeout.min(MAX_REQUEST_TIMEOUT)
+    }
+
+    /// Stores a fragment received from `sender`, returning whether it was accepted. Fragments
+    /// from peers the chunk was not requested from, and fragments whose count disagrees with
+    /// the ones received before, are ignored.
+    pub fn store_fragment(
+        &mut self,
+        sender: SocketAddr,
+        fragment_index: u16,
+        fragment_count: u16,
+        fragment: Vec<u8>,
+    ) -> bool {
+        if !self.requested_from.contains(&sender) {
+            return false;
+        }
+
+        if self.fragments.is_empty() {
+            self.fragments = vec![None; fragment_count as usize];
+        }
+
+        if self.fragments.len() != fragment_count as usize {
+            return false;
+        }
+
+        self.fragments[fragment_index as usize] = Some(fragment);
+        self.last_activity = Some(Instant::now());
+        self.retries = 0;
+        if !self.contributors.contains(&sender) {
+            self.contributors.push(sender);
+        }
+        true
+    }
+
+    pub fn missing_fragments(&self) -> Vec<u16> {
+        self.fragments
+            .iter()
+            .enumerate()
+            .filter(|(_index, fragment)| fragment.is_none())
+            .map(|(index, _fragment)| index as u16)
+            .collect()
+    }
+
+    pub fn has_all_fragments(&self) -> bool {
+        !self.fragments.is_empty() && self.fragments.iter().all(Option::is_some)
+    }
+
+    /// Concatenates the received fragments into the full chunk, releasing them.
+    pub fn take_chunk(&mut self) -> Vec<u8> {
+        self.fragments
+            .drain(..)
+            .flat_map(|fragment| fragment.unwrap_or_default())
+            .collect()
+    }
+
+    /// Discards an assembled copy of the chunk that failed verification, so that it is
+    /// requested again from scratch. Returns the peers that contributed to it, which are not
+    /// asked for this chunk again.
+    pub fn reject(&mut self) -> Vec<SocketAddr> {
+        let contributors: Vec<SocketAddr> = self.contributors.drain(..).collect();
+        for contributor in &contributors {
+            if !self.rejected.contains(contributor) {
+                self.rejected.push(*contributor);
+            }
+        }
+
+        self.received = false;
+        self.source = None;
+        self.requested_from.clear();
+        self.fragments.clear();
+        self.last_activity = None;
+
+        contributors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requested(ago: Duration, retries: u32) -> ChunkControlData {
+        let source = SocketAddr::from(([127, 0, 0, 1], 7000));
+        ChunkControlData {
+            source: Some(source),
+            requested_from: vec![source],
+            last_activity: Instant::now().checked_sub(ago),
+            retries,
+            ..ChunkControlData::default()
+        }
+    }
+
+    #[test]
+    fn request_timeout_doubles_up_to_the_maximum() {
+        let timeouts: Vec<Duration> = [0, 1, 2, 3, 20]
+            .iter()
+            .map(|&retries| requested(Duration::ZERO, retries).request_timeout())
+            .collect();
+
+        assert_eq!(
+            timeouts,
+            vec![
+                Duration::from_millis(250),
+                Duration::from_millis(500),
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                MAX_REQUEST_TIMEOUT,
+            ]
+        );
+    }
+
+    #[test]
+    fn request_is_stalled_once_its_timeout_passes() {
+        assert!(!requested(Duration::from_millis(100), 0).is_stalled());
+        assert!(requested(Duration::from_millis(300), 0).is_stalled());
+        assert!(!requested(Duration::from_millis(300), 1).is_stalled());
+    }
+
+    #[test]
+    fn chunks_never_requested_or_already_received_are_not_stalled() {
+        assert!(!ChunkControlData::default().is_stalled());
+
+        let mut received = requested(Duration::from_secs(10), 0);
+        received.received = true;
+        assert!(!received.is_stalled());
+    }
+
+    #[test]
+    fn fragments_reset_the_backoff() {
+        let mut chunk = requested(Duration::from_secs(10), 3);
+        chunk.store_fragment(SocketAddr::from(([127, 0, 0, 1], 7000)), 0, 2, vec![1]);
+
+        assert_eq!(chunk.retries, 0);
+        assert!(!chunk.is_stalled());
+        assert_eq!(chunk.missing_fragments(), vec![1]);
+    }
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn reject_distrusts_the_contributors_and_resets_the_chunk() {
+        let mut chunk = ChunkControlData::default();
+        chunk.add_provider(peer(1));
+        chunk.add_provider(peer(2));
+        chunk.add_provider(peer(3));
+        chunk.mark_requested(peer(1));
+        chunk.mark_requested(peer(2));
+        chunk.store_fragment(peer(1), 0, 2, vec![1]);
+        chunk.store_fragment(peer(2), 1, 2, vec![2]);
+        chunk.take_chunk();
+
+        assert_eq!(chunk.reject(), vec![peer(1), peer(2)]);
+
+        assert_eq!(chunk.trusted_providers(), vec![peer(3)]);
+        assert!(!chunk.sent_get());
+        assert!(chunk.fragments.is_empty());
+        assert!(chunk.contributors.is_empty());
+        assert!(!chunk.is_stalled());
+    }
+
+    #[test]
+    fn rejecting_every_provider_leaves_no_trusted_one() {
+        let mut chunk = ChunkControlData::default();
+        chunk.add_provider(peer(1));
+        chunk.mark_requested(peer(1));
+        chunk.store_fragment(peer(1), 0, 1, vec![1]);
+        chunk.reject();
+
+        // The same peer advertising the chunk again is still not trusted.
+        chunk.add_provider(peer(1));
+        chunk.mark_requested(peer(1));
+        chunk.store_fragment(peer(1), 0, 1, vec![1]);
+        assert_eq!(chunk.reject(), vec![peer(1)]);
+
+        assert_eq!(chunk.rejected, vec![peer(1)]);
+        assert_eq!(chunk.providers, vec![peer(1)]);
+        assert!(chunk.trusted_providers().is_empty());
+    }
+
+    #[test]
+    fn fragments_are_only_taken_from_peers_asked_for_the_chunk() {
+        let mut chunk = ChunkControlData::default();
+        chunk.add_provider(peer(1));
+        chunk.add_provider(peer(2));
+
+        assert!(!chunk.store_fragment(peer(1), 0, 2, vec![1]));
+        chunk.mark_requested(peer(1));
+        assert!(!chunk.store_fragment(peer(2), 0, 2, vec![2]));
+        assert!(chunk.store_fragment(peer(1), 0, 2, vec![1]));
+
+        // A retry with another peer still takes the late fragments of the first one.
+        chunk.mark_requested(peer(2));
+        assert!(chunk.store_fragment(peer(1), 1, 2, vec![1]));
+        assert_eq!(chunk.contributors, vec![peer(1)]);
+    }
+
+    #[test]
+    fn fragments_of_a_rejected_copy_are_not_taken() {
+        let mut chunk = ChunkControlData::default();
+        chunk.mark_requested(peer(1));
+        chunk.store_fragment(peer(1), 0, 2, vec![1]);
+        chunk.reject();
+
+        assert!(!chunk.store_fragment(peer(1), 1, 2, vec![1]));
+        assert!(chunk.fragments.is_empty());
+    }
 }
@@ -0,0 +1,106 @@
+use common::{ChunkId, ContentId};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fs};
+
+pub type ChunkHash = [u8; 32];
+
+/// Expected SHA-256 of each chunk, read from a trusted manifest with one `<id>: <hex hash>`
+/// line per chunk of the default content and one `<content>/<id>: <hex hash>` line per chunk
+/// of other contents. Blank lines and lines starting with `#` are ignored.
+///
+/// The hashes are not taken from the peers: a peer that corrupts a chunk could just as well
+/// advertise the hash of the corrupted bytes.
+#[derive(Debug, Default)]
+pub struct ChunkHashes {
+    hashes: HashMap<(ContentId, ChunkId), ChunkHash>,
+}
+
+impl ChunkHashes {
+    pub fn load(path: &str) -> ChunkHashes {
+        let contents = fs::read_to_string(path).expect("Failed to read chunk hashes file");
+        ChunkHashes::parse(&contents)
+    }
+
+    fn parse(contents: &str) -> ChunkHashes {
+        let mut hashes = HashMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, hash) = line
+                .split_once(':')
+                .unwrap_or_else(|| panic!("Line {} of chunk hashes file has no ':'", index + 1));
+            let key = key.trim();
+            let (content, chunk) = match key.rfind('/') {
+                Some(slash) => (&key[..slash], &key[slash + 1..]),
+                None => ("", key),
+            };
+            let content = match ContentId::new(content) {
+                Ok(content) if !content.is_default() || !key.contains('/') => content,
+                _ => panic!("Invalid content name on line {} of hashes file", index + 1),
+            };
+            let chunk = chunk.parse().unwrap_or_else(|_| {
+                panic!("Invalid chunk number on line {} of hashes file", index + 1)
+            });
+            let hash = parse_hex(hash.trim())
+                .unwrap_or_else(|| panic!("Invalid SHA-256 on line {} of hashes file", index + 1));
+
+            hashes.insert((content, chunk), hash);
+        }
+
+        ChunkHashes { hashes }
+    }
+
+    /// Whether `chunk` matches the expected hash. Chunks without a known hash are accepted.
+    pub fn verify(&self, content: &ContentId, chunk_id: ChunkId, chunk: &[u8]) -> bool {
+        match self.hashes.get(&(content.clone(), chunk_id)) {
+            Some(expected) => Sha256::digest(chunk).as_slice() == expected,
+            None => true,
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<ChunkHash> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (index, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+    }
+
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn hashes_are_kept_per_content() {
+        let hashes = ChunkHashes::parse(&format!(
+            "# hashes\n\n1: {}\nsintel/720p/2: {}\n",
+            EMPTY_SHA256, EMPTY_SHA256
+        ));
+        let sintel = ContentId::new("sintel/720p").unwrap();
+
+        assert!(hashes.verify(&ContentId::default(), 1, b""));
+        assert!(!hashes.verify(&ContentId::default(), 1, b"forged"));
+        assert!(hashes.verify(&sintel, 2, b""));
+        assert!(!hashes.verify(&sintel, 2, b"forged"));
+        // Neither has a known hash: the default content's chunk 2 or sintel's chunk 1.
+        assert!(hashes.verify(&ContentId::default(), 2, b"anything"));
+        assert!(hashes.verify(&sintel, 1, b"anything"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid content name on line 1")]
+    fn keys_with_an_empty_content_name_are_rejected() {
+        ChunkHashes::parse(&format!("/1: {}", EMPTY_SHA256));
+    }
+}
@@ -1,12 +1,34 @@
+use common::{ChunkId, ContentId, MAX_CHUNK_LIST_LENGTH};
 use std::{env, net::SocketAddr};
 
 #[derive(Debug)]
 pub struct ClientConfig {
     pub address: SocketAddr,
-    pub chunks: Vec<u16>,
+    /// Video the chunks belong to.
+    pub content: ContentId,
+    pub chunks: Vec<ChunkId>,
+    /// TTL asked for in the Hello. 0 lets the peer use its own default.
+    pub query_ttl: u16,
+    /// When set, the search starts with `query_ttl` (or 1) and the Hello is sent again with
+    /// a larger TTL, up to this value, while some chunks have no known provider.
+    pub expanding_ring_max_ttl: Option<u16>,
+    /// File with the expected SHA-256 of the chunks.
+    pub hashes_path: Option<String>,
+    /// Tracker asked for providers first. Chunks it knows no provider for are searched for
+    /// by flooding through `address`.
+    pub tracker_address: Option<SocketAddr>,
+    /// Whether providers are looked up in the DHT, starting from `address`. Chunks without
+    /// providers there are searched for by flooding.
+    pub dht: bool,
+    /// Whether the client stays on after the download, serving the chunks it received.
+    pub seed: bool,
 }
 
 impl ClientConfig {
+    /// Parses `<peer address> <chunks>`, where chunks are a comma-separated list of numbers and
+    /// inclusive ranges such as `1-200,305`, optionally followed by `--ttl <n>`,
+    /// `--expanding-ring <max ttl>`, `--hashes <file>`, `--content <name>`,
+    /// `--tracker <address>`, `--dht` and `--seed`.
     pub fn new(mut args: env::Args) -> ClientConfig {
         args.next();
 
@@ -14,13 +36,171 @@ impl ClientConfig {
 
         let address: SocketAddr = address.parse().expect("Failed to parse peer address");
 
-        let chunks = args.next().expect("Chunk numbers not specified");
+        let chunks = parse_chunks(&args.next().expect("Chunk numbers not specified"));
 
-        let chunks = chunks
-            .split(",")
-            .map(|chunk| chunk.parse::<u16>().expect("Failed to parse chunk numbers"))
-            .collect();
+        let mut query_ttl = 0;
+        let mut expanding_ring_max_ttl = None;
+        let mut hashes_path = None;
+        let mut content = ContentId::default();
+        let mut tracker_address: Option<SocketAddr> = None;
+        let mut dht = false;
+        let mut seed = false;
 
-        return ClientConfig { address, chunks };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--ttl" => {
+                    query_ttl = args
+                        .next()
+                        .expect("TTL not specified")
+                        .parse()
+                        .expect("Failed to parse TTL");
+                }
+                "--expanding-ring" => {
+                    let max_ttl = args
+                        .next()
+                        .expect("Maximum TTL not specified")
+                        .parse()
+                        .expect("Failed to parse maximum TTL");
+                    expanding_ring_max_ttl = Some(max_ttl);
+                }
+                "--hashes" => {
+                    hashes_path = Some(args.next().expect("Chunk hashes file not specified"));
+                }
+                "--content" => {
+                    let name = args.next().expect("Content name not specified");
+                    content = ContentId::new(&name).expect("Invalid content name");
+                }
+                "--tracker" => {
+                    let tracker = args.next().expect("Tracker address not specified");
+                    tracker_address =
+                        Some(tracker.parse().expect("Failed to parse tracker address"));
+                }
+                "--dht" => dht = true,
+                "--seed" => seed = true,
+                _ => panic!("Unknown argument {}", arg),
+            }
+        }
+
+        if dht && tracker_address.is_some() {
+            panic!("--dht and --tracker cannot be used together");
+        }
+
+        // The socket is bound to the peer's address family, and IPv4 sockets cannot send to
+        // IPv6 hosts.
+        if let Some(tracker) = tracker_address {
+            if tracker.is_ipv6() && address.is_ipv4() {
+                panic!(
+                    "Tracker {} is an IPv6 address but peer {} is IPv4",
+                    tracker, address
+                );
+            }
+        }
+
+        if expanding_ring_max_ttl.is_some() && query_ttl == 0 {
+            query_ttl = 1;
+        }
+
+        ClientConfig {
+            address,
+            content,
+            chunks,
+            query_ttl,
+            expanding_ring_max_ttl,
+            hashes_path,
+            tracker_address,
+            dht,
+            seed,
+        }
+    }
+}
+
+/// Parses a list such as `1-200,305` into sorted, distinct chunk IDs, which lets the requests
+/// use the compact chunk-list encodings. Peers accept at most `MAX_CHUNK_LIST_LENGTH` chunks
+/// per list, so longer lists are refused.
+fn parse_chunks(chunks: &str) -> Vec<ChunkId> {
+    let parse = |chunk: &str| {
+        chunk
+            .trim()
+            .parse::<ChunkId>()
+            .expect("Failed to parse chunk numbers")
+    };
+
+    let mut parsed = Vec::new();
+    for item in chunks.split(',') {
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first), parse(last));
+                if first > last {
+                    panic!("Invalid chunk range {}", item);
+                }
+                if (last - first) as usize >= MAX_CHUNK_LIST_LENGTH {
+                    panic!(
+                        "Chunk range {} is longer than {} chunks",
+                        item, MAX_CHUNK_LIST_LENGTH
+                    );
+                }
+                parsed.extend(first..=last);
+            }
+            None => parsed.push(parse(item)),
+        }
+    }
+
+    parsed.sort_unstable();
+    parsed.dedup();
+    if parsed.len() > MAX_CHUNK_LIST_LENGTH {
+        panic!(
+            "{} chunks requested, at most {} are supported",
+            parsed.len(),
+            MAX_CHUNK_LIST_LENGTH
+        );
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_numbers_and_ranges_into_sorted_distinct_chunks() {
+        assert_eq!(parse_chunks("7"), vec![7]);
+        assert_eq!(parse_chunks("9, 1-3,2 ,5-5"), vec![1, 2, 3, 5, 9]);
+        assert_eq!(parse_chunks("70000-70001"), vec![70_000, 70_001]);
+    }
+
+    #[test]
+    fn accepts_the_longest_list_peers_accept() {
+        assert_eq!(
+            parse_chunks(&format!("1-{}", MAX_CHUNK_LIST_LENGTH)).len(),
+            MAX_CHUNK_LIST_LENGTH
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "longer than")]
+    fn rejects_ranges_longer_than_peers_accept() {
+        parse_chunks("1-2000000");
+    }
+
+    #[test]
+    #[should_panic(expected = "at most")]
+    fn rejects_lists_longer_than_peers_accept() {
+        parse_chunks(&format!(
+            "0-{},{}",
+            MAX_CHUNK_LIST_LENGTH - 1,
+            MAX_CHUNK_LIST_LENGTH
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid chunk range")]
+    fn rejects_inverted_ranges() {
+        parse_chunks("5-3");
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to parse chunk numbers")]
+    fn rejects_non_numbers() {
+        parse_chunks("1,x");
     }
 }
@@ -29,12 +29,11 @@ impl Logger {
 
     pub fn log(&self, content: String) {
         let mut log_file = OpenOptions::new()
-            .write(true)
             .append(true)
             .open(&self.log_file_path)
             .expect("Failed to open log file");
         log_file
-            .write(&content.into_bytes())
+            .write_all(&content.into_bytes())
             .expect("Failed to write to log");
     }
 }
@@ -1,11 +1,22 @@
-use common::{ChunkListMessage, Message, ResponseInfo};
+use common::{
+    ChunkId, ChunkList, ChunkListMessage, ContentId, FragmentRequestInfo, HelloInfo, Message,
+    MessageType, ResponseInfo,
+};
 use core::panic;
+use peer::{
+    chunk_store::{ChunkStore, StoreKind},
+    directory_store::DirectoryStore,
+    peer_config::PeerConfig,
+    Peer,
+};
 use std::{
     collections::HashMap,
     env,
-    fs::File,
+    fs::{self, File},
     io::{ErrorKind, Write},
     net::{SocketAddr, UdpSocket},
+    path::Path,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -15,43 +26,144 @@ use client_config::ClientConfig;
 mod logger;
 use logger::Logger;
 
+mod chunk_hashes;
+use chunk_hashes::ChunkHashes;
+
 mod chunk_control_data;
 use chunk_control_data::ChunkControlData;
 
+mod peer_table;
+use peer_table::PeerTable;
+
+mod provider_search;
+use provider_search::ProviderSearch;
+
 fn main() {
     let config = ClientConfig::new(env::args());
     let mut chunks_status = create_chunks_status_map(&config);
+    let chunk_hashes = match &config.hashes_path {
+        Some(path) => ChunkHashes::load(path),
+        None => ChunkHashes::default(),
+    };
 
-    let udp_socket = create_udp_socket();
+    let udp_socket = create_udp_socket(&config.address);
     let local_ip = udp_socket
         .local_addr()
         .expect("Failed to get local address")
         .ip();
     let logger = Logger::new(local_ip);
 
-    let hello_message = create_hello_message(&config);
-
-    udp_socket
-        .send_to(&hello_message.serialize(), config.address)
-        .expect("Failed to send message");
+    let mut peer_table = PeerTable::new();
+    let mut query_ttl = config.query_ttl;
+    let mut flooding = config.tracker_address.is_none() && !config.dht;
+    let mut provider_search = None;
+    if config.dht {
+        provider_search = Some(ProviderSearch::new(
+            &config.content,
+            &config.chunks,
+            config.address,
+        ));
+    } else if let Some(tracker_address) = config.tracker_address {
+        ask_tracker(
+            &udp_socket,
+            &config.content,
+            &tracker_address,
+            config.chunks.clone(),
+            &mut peer_table,
+        );
+    } else {
+        send_hello(
+            &udp_socket,
+            &config,
+            query_ttl,
+            config.chunks.clone(),
+            &mut peer_table,
+        );
+    }
+    let mut last_hello = Instant::now();
 
     let mut all_chunks_received = false;
     let start = Instant::now();
 
     while !all_chunks_received && !timed_out(&start) {
+        if let Some(provider_search) = &mut provider_search {
+            provider_search.step(&udp_socket);
+        }
+
+        let search_over = match &provider_search {
+            Some(provider_search) => provider_search.is_finished(),
+            None => last_hello.elapsed() > TRACKER_TIMEOUT,
+        };
+        if !flooding && search_over {
+            flooding = true;
+            fall_back_to_flooding(
+                &udp_socket,
+                &config,
+                query_ttl,
+                &chunks_status,
+                &mut peer_table,
+            );
+            last_hello = Instant::now();
+        }
+
+        if let (true, Some(max_ttl)) = (flooding, config.expanding_ring_max_ttl) {
+            if query_ttl < max_ttl && last_hello.elapsed() > EXPANDING_RING_INTERVAL {
+                expand_search(
+                    &udp_socket,
+                    &config,
+                    &mut query_ttl,
+                    &chunks_status,
+                    &mut peer_table,
+                );
+                last_hello = Instant::now();
+            }
+        }
+
+        schedule_requests(
+            &udp_socket,
+            &config.content,
+            &mut chunks_status,
+            &mut peer_table,
+        );
+
         let mut buffer = [0; 60 * 1024];
 
         let result = udp_socket.recv_from(&mut buffer);
         match result {
             Ok((bytes_read, peer_address)) => {
-                handle_message(
-                    &buffer,
-                    bytes_read,
-                    &udp_socket,
-                    peer_address,
-                    &mut chunks_status,
-                    &logger,
-                );
+                let peer_address = common::canonical_address(peer_address);
+                println!("Received {} bytes", bytes_read);
+                let message = match Message::new(&buffer, bytes_read) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        eprintln!("Dropping malformed message from {}: {}", peer_address, err);
+                        continue;
+                    }
+                };
+
+                match (message, &mut provider_search) {
+                    (Message::Nodes(data), Some(provider_search)) => {
+                        if let Some((chunk, providers)) =
+                            provider_search.handle_nodes(data, peer_address)
+                        {
+                            handle_found_providers(
+                                chunk,
+                                providers,
+                                &mut chunks_status,
+                                &mut peer_table,
+                            );
+                        }
+                    }
+                    (message, _) => handle_message(
+                        message,
+                        peer_address,
+                        &config,
+                        &mut chunks_status,
+                        &mut peer_table,
+                        &chunk_hashes,
+                        &logger,
+                    ),
+                }
             }
             Err(ref err) if err.kind() != ErrorKind::WouldBlock => {
                 panic!("Failed to read from udp socket");
@@ -70,37 +182,81 @@ fn main() {
 
     chunks_status
         .iter()
-        .filter(|(&_chunk, &chunk_control_data)| !chunk_control_data.received)
-        .map(|(&chunk, &_chunk_control_data)| format!("0.0.0.0:0 - {}\n", chunk))
+        .filter(|(_chunk, chunk_control_data)| !chunk_control_data.received)
+        .map(|(chunk, _chunk_control_data)| format!("0.0.0.0:0 - {}\n", chunk))
         .for_each(|line| {
             logger.log(line);
         });
+
+    if config.seed {
+        let received = chunks_status
+            .iter()
+            .filter(|(_chunk, chunk_control_data)| chunk_control_data.received)
+            .map(|(chunk, _chunk_control_data)| *chunk)
+            .collect();
+        seed(&config, udp_socket, received);
+    }
     println!("Exiting...");
 }
 
 fn handle_message(
-    buffer: &[u8],
-    bytes_read: usize,
-    udp_socket: &UdpSocket,
+    message: Message,
     peer_address: SocketAddr,
-    chunks_status: &mut HashMap<u16, ChunkControlData>,
+    config: &ClientConfig,
+    chunks_status: &mut HashMap<ChunkId, ChunkControlData>,
+    peer_table: &mut PeerTable,
+    chunk_hashes: &ChunkHashes,
     logger: &Logger,
 ) {
-    println!("Received {} bytes", bytes_read);
-    let message = Message::new(&buffer, bytes_read).expect("Failed to parse message");
+    let content = &config.content;
     match message {
+        // Anyone could otherwise point the client at providers of their choosing.
+        Message::TrackerReply(_) if config.tracker_address != Some(peer_address) => {
+            println!(
+                "Ignoring TrackerReply from {}, which is not the tracker",
+                peer_address
+            );
+        }
+        Message::ChunkInfo(data) if data.content != *content => {
+            println!("Ignoring ChunkInfo for content {}", data.content);
+        }
+        Message::Response(data) if data.content != *content => {
+            println!("Ignoring Response for content {}", data.content);
+        }
+        Message::TrackerReply(data) if data.content != *content => {
+            println!("Ignoring TrackerReply for content {}", data.content);
+        }
         Message::ChunkInfo(data) => {
-            handle_chunk_info(&udp_socket, &data, &peer_address, chunks_status);
+            println!("Got ChunkInfo message!");
+            peer_table.record_advertisement(peer_address);
+            handle_chunk_info(&data.chunk_list, &peer_address, chunks_status);
+        }
+        Message::TrackerReply(data) => {
+            println!("Got TrackerReply message from {}!", peer_address);
+            peer_table.record_listed_provider(data.address);
+            handle_chunk_info(&data.chunk_list, &data.address, chunks_status);
         }
         Message::Response(data) => {
-            handle_response(data, logger, &peer_address, chunks_status);
+            handle_response(
+                data,
+                logger,
+                &peer_address,
+                chunks_status,
+                peer_table,
+                chunk_hashes,
+            );
         }
         _ => {}
     }
 }
 
-fn create_udp_socket() -> UdpSocket {
-    let udp_socket = UdpSocket::bind(("0.0.0.0", 0)).unwrap();
+fn create_udp_socket(peer_address: &SocketAddr) -> UdpSocket {
+    let local_address = if peer_address.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let udp_socket = UdpSocket::bind(local_address).unwrap();
     udp_socket
         .set_nonblocking(true)
         .expect("Failed to set socket to nonblocking mode.");
@@ -108,10 +264,125 @@ fn create_udp_socket() -> UdpSocket {
     udp_socket
 }
 
-fn create_hello_message(config: &ClientConfig) -> ChunkListMessage {
-    ChunkListMessage::from_chunks(1, config.chunks.clone())
+fn send_hello(
+    udp_socket: &UdpSocket,
+    config: &ClientConfig,
+    query_ttl: u16,
+    chunks: Vec<ChunkId>,
+    peer_table: &mut PeerTable,
+) {
+    let hello_message = HelloInfo::from_chunks(config.content.clone(), query_ttl, chunks);
+
+    send_to(udp_socket, &hello_message.serialize(), &config.address);
+    peer_table.hello_sent();
+}
+
+/// Expanding-ring search: asks again, with a TTL one hop larger, for the chunks that no
+/// trusted peer advertised so far.
+fn expand_search(
+    udp_socket: &UdpSocket,
+    config: &ClientConfig,
+    query_ttl: &mut u16,
+    chunks_status: &HashMap<ChunkId, ChunkControlData>,
+    peer_table: &mut PeerTable,
+) {
+    let unlocated_chunks = unlocated_chunks(chunks_status);
+    if unlocated_chunks.is_empty() {
+        return;
+    }
+
+    *query_ttl += 1;
+    println!(
+        "No peer has chunks {:?} yet, searching again with TTL {}",
+        unlocated_chunks, query_ttl
+    );
+    send_hello(udp_socket, config, *query_ttl, unlocated_chunks, peer_table);
+}
+
+/// Asks the tracker which peers hold `chunks`.
+fn ask_tracker(
+    udp_socket: &UdpSocket,
+    content: &ContentId,
+    tracker_address: &SocketAddr,
+    chunks: Vec<ChunkId>,
+    peer_table: &mut PeerTable,
+) {
+    let query = ChunkListMessage::from_chunks(MessageType::TrackerQuery, content.clone(), chunks);
+
+    send_to(udp_socket, &query.serialize(), tracker_address);
+    peer_table.hello_sent();
+}
+
+/// Searches by flooding for the chunks the tracker or the DHT knows no provider for.
+fn fall_back_to_flooding(
+    udp_socket: &UdpSocket,
+    config: &ClientConfig,
+    query_ttl: u16,
+    chunks_status: &HashMap<ChunkId, ChunkControlData>,
+    peer_table: &mut PeerTable,
+) {
+    let unlocated_chunks = unlocated_chunks(chunks_status);
+    if unlocated_chunks.is_empty() {
+        return;
+    }
+
+    println!(
+        "No provider known for chunks {:?}, flooding",
+        unlocated_chunks
+    );
+    send_hello(udp_socket, config, query_ttl, unlocated_chunks, peer_table);
+}
+
+/// Records the providers of `chunk` found in the DHT.
+fn handle_found_providers(
+    chunk: ChunkId,
+    providers: Vec<SocketAddr>,
+    chunks_status: &mut HashMap<ChunkId, ChunkControlData>,
+    peer_table: &mut PeerTable,
+) {
+    println!(
+        "Found {} providers of chunk {} in the DHT",
+        providers.len(),
+        chunk
+    );
+
+    if let Some(chunk_control_data) = chunks_status.get_mut(&chunk) {
+        for provider in providers {
+            peer_table.record_listed_provider(provider);
+            chunk_control_data.add_provider(provider);
+        }
+    }
 }
 
+/// Chunks not received yet that no trusted peer advertised so far, including those whose
+/// every provider sent corrupt data.
+fn unlocated_chunks(chunks_status: &HashMap<ChunkId, ChunkControlData>) -> Vec<ChunkId> {
+    let mut chunks: Vec<ChunkId> = chunks_status
+        .iter()
+        .filter(|(_chunk, chunk_control_data)| {
+            !chunk_control_data.received && chunk_control_data.trusted_providers().is_empty()
+        })
+        .map(|(chunk, _chunk_control_data)| *chunk)
+        .collect();
+    chunks.sort_unstable();
+
+    chunks
+}
+
+/// How long to wait for the tracker's replies before flooding for the chunks it did not
+/// list.
+const TRACKER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the expanding-ring search waits for advertisements before trying a larger TTL.
+const EXPANDING_RING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Keeps re-requests for a single chunk within one datagram.
+const MAX_FRAGMENTS_PER_REQUEST: usize = 1024;
+
+/// How long to wait, after a chunk's first advertisement, for other peers to advertise it too
+/// before choosing where to request it from.
+const PROVIDER_COLLECTION_WINDOW: Duration = Duration::from_millis(100);
+
 fn timed_out(start: &Instant) -> bool {
     let time_elapsed = Instant::now() - *start;
     let timed_out = time_elapsed > Duration::from_secs(5);
@@ -123,36 +394,28 @@ fn timed_out(start: &Instant) -> bool {
     timed_out
 }
 
-fn create_chunks_status_map(config: &ClientConfig) -> HashMap<u16, ChunkControlData> {
+fn create_chunks_status_map(config: &ClientConfig) -> HashMap<ChunkId, ChunkControlData> {
     config
         .chunks
         .iter()
-        .map(|chunk| {
-            (
-                *chunk,
-                ChunkControlData {
-                    received: false,
-                    sent_get: false,
-                },
-            )
-        })
+        .map(|chunk| (*chunk, ChunkControlData::default()))
         .collect()
 }
 
+/// Records `remote_addr` as a provider of the chunks in `chunk_list`.
 fn handle_chunk_info(
-    udp_socket: &UdpSocket,
-    data: &ChunkListMessage,
+    chunk_list: &ChunkList,
     remote_addr: &SocketAddr,
-    chunks_status: &mut HashMap<u16, ChunkControlData>,
+    chunks_status: &mut HashMap<ChunkId, ChunkControlData>,
 ) {
     println!(
-        "Got ChunkInfo message! Peer {} has {} chunks",
+        "Peer {} has {} chunks",
         remote_addr,
-        data.chunk_list.chunks.len()
+        chunk_list.chunks.len()
     );
     println!(
         "{}",
-        data.chunk_list
+        chunk_list
             .chunks
             .iter()
             .map(|c| c.to_string())
@@ -160,53 +423,190 @@ fn handle_chunk_info(
             .join(",")
     );
 
-    if !data.chunk_list.chunks.is_empty() {
-        let mut needed_chunks = Vec::new();
+    for chunk in &chunk_list.chunks {
+        if let Some(chunk_control_data) = chunks_status.get_mut(chunk) {
+            chunk_control_data.add_provider(*remote_addr);
+        }
+    }
+}
+
+/// Decides which chunks must be requested now and from which peer. Chunks are requested once
+/// their providers had time to advertise them, and requested again when they made no progress
+/// within their backoff interval. Every chunk goes to the cheapest provider given its RTT,
+/// past failures and the requests already assigned to it, which spreads the load over all the
+/// peers that have the chunk.
+fn schedule_requests(
+    udp_socket: &UdpSocket,
+    content: &ContentId,
+    chunks_status: &mut HashMap<ChunkId, ChunkControlData>,
+    peer_table: &mut PeerTable,
+) {
+    let mut load: HashMap<SocketAddr, usize> = HashMap::new();
+    for chunk_control_data in chunks_status.values() {
+        if let (false, Some(source)) = (chunk_control_data.received, chunk_control_data.source) {
+            *load.entry(source).or_default() += 1;
+        }
+    }
 
-        for chunk in &data.chunk_list.chunks {
-            let value = chunks_status.get(chunk);
-            let should_include_chunk_in_get_message = match value {
-                Some(chunk_control_data) => !chunk_control_data.sent_get,
-                None => true,
-            };
+    let mut chunks: Vec<ChunkId> = chunks_status.keys().copied().collect();
+    chunks.sort_unstable();
 
-            if should_include_chunk_in_get_message {
-                needed_chunks.push(*chunk);
+    let mut gets_by_provider: HashMap<SocketAddr, Vec<ChunkId>> = HashMap::new();
+
+    for chunk in chunks {
+        let chunk_control_data = chunks_status.get_mut(&chunk).expect("Unknown error");
+        if chunk_control_data.received {
+            continue;
+        }
+
+        let first_request = match (
+            chunk_control_data.sent_get(),
+            chunk_control_data.first_advertised_at,
+        ) {
+            (false, Some(first_advertised_at)) => {
+                first_advertised_at.elapsed() >= PROVIDER_COLLECTION_WINDOW
+            }
+            _ => false,
+        };
+        let stalled = chunk_control_data.is_stalled();
+        if !first_request && !stalled {
+            continue;
+        }
+
+        // A peer that sent nothing at all is treated as failed and avoided, while one that
+        // sent part of the chunk is only missing some fragments lost on the way.
+        let previous_source = chunk_control_data.source;
+        let unresponsive_source = match previous_source {
+            Some(source) if stalled && chunk_control_data.fragments.is_empty() => Some(source),
+            _ => None,
+        };
+        if stalled {
+            if let Some(previous_source) = previous_source {
+                if let Some(count) = load.get_mut(&previous_source) {
+                    *count = count.saturating_sub(1);
+                }
+            }
+            chunk_control_data.retries += 1;
+        }
+        if let Some(unresponsive_source) = unresponsive_source {
+            peer_table.record_failure(unresponsive_source);
+        }
+
+        let provider = match peer_table.best_provider(
+            &chunk_control_data.trusted_providers(),
+            unresponsive_source,
+            &load,
+        ) {
+            Some(provider) => provider,
+            None => continue,
+        };
+        *load.entry(provider).or_default() += 1;
+
+        if chunk_control_data.fragments.is_empty() {
+            if stalled {
+                println!(
+                    "Retrying chunk {} with peer {} (attempt {})",
+                    chunk,
+                    provider,
+                    chunk_control_data.retries + 1
+                );
             }
+            gets_by_provider.entry(provider).or_default().push(chunk);
+            continue;
         }
 
-        if !needed_chunks.is_empty() {
-            let get_message = ChunkListMessage::from_chunks(4, needed_chunks.clone());
+        let mut missing_fragments = chunk_control_data.missing_fragments();
+        missing_fragments.truncate(MAX_FRAGMENTS_PER_REQUEST);
+        println!(
+            "Requesting {} missing fragments of chunk {} from peer {}",
+            missing_fragments.len(),
+            chunk,
+            provider
+        );
+
+        let request =
+            FragmentRequestInfo::from_fragments(content.clone(), chunk, missing_fragments);
+        send_to(udp_socket, &request.serialize(), &provider);
+        chunk_control_data.mark_requested(provider);
+    }
 
-            udp_socket
-                .send_to(&get_message.serialize(), remote_addr)
-                .expect("Falha ao enviar mensagem");
+    for (provider, chunks) in gets_by_provider {
+        println!("Requesting chunks {:?} from peer {}", chunks, provider);
+        let get_message =
+            ChunkListMessage::from_chunks(MessageType::Get, content.clone(), chunks.clone());
+        send_to(udp_socket, &get_message.serialize(), &provider);
 
-            for chunk in &needed_chunks {
-                chunks_status
-                    .get_mut(chunk)
-                    .expect("Unknown error")
-                    .sent_get = true;
+        for chunk in &chunks {
+            if let Some(chunk_control_data) = chunks_status.get_mut(chunk) {
+                chunk_control_data.mark_requested(provider);
             }
         }
     }
 }
 
+fn send_to(udp_socket: &UdpSocket, data: &[u8], remote_addr: &SocketAddr) {
+    let local_address = udp_socket
+        .local_addr()
+        .expect("Failed to get local address");
+    if let Err(err) = udp_socket.send_to(
+        data,
+        common::reachable_address(&local_address, *remote_addr),
+    ) {
+        eprintln!("Failed to send message to {}: {}", remote_addr, err);
+    }
+}
+
 fn handle_response(
     data: ResponseInfo,
     logger: &Logger,
     remote_addr: &SocketAddr,
-    chunks_status: &mut HashMap<u16, ChunkControlData>,
+    chunks_status: &mut HashMap<ChunkId, ChunkControlData>,
+    peer_table: &mut PeerTable,
+    chunk_hashes: &ChunkHashes,
 ) {
     println!(
-        "Received chunk {} from peer {}.",
-        data.chunk_id, remote_addr
+        "Received fragment {} of chunk {} from peer {}.",
+        data.fragment_index, data.chunk_id, remote_addr
     );
 
-    chunks_status
-        .get_mut(&data.chunk_id)
-        .expect("Received unwanted chunk")
-        .received = true;
+    let chunk_control_data = match chunks_status.get_mut(&data.chunk_id) {
+        Some(chunk_control_data) if !chunk_control_data.received => chunk_control_data,
+        _ => {
+            println!("Ignoring unwanted chunk {}", data.chunk_id);
+            return;
+        }
+    };
+
+    let stored = chunk_control_data.store_fragment(
+        *remote_addr,
+        data.fragment_index,
+        data.fragment_count,
+        data.fragment,
+    );
+    if !stored {
+        println!(
+            "Ignoring fragment {} of chunk {} from peer {}",
+            data.fragment_index, data.chunk_id, remote_addr
+        );
+        return;
+    }
+    if !chunk_control_data.has_all_fragments() {
+        return;
+    }
+
+    let chunk = chunk_control_data.take_chunk();
+    if !chunk_hashes.verify(&data.content, data.chunk_id, &chunk) {
+        let contributors = chunk_control_data.reject();
+        println!(
+            "Chunk {} failed hash verification, discarding data from peers {:?}",
+            data.chunk_id, contributors
+        );
+        for contributor in contributors {
+            peer_table.record_corrupt_chunk(contributor);
+        }
+        return;
+    }
+    chunk_control_data.received = true;
 
     let peer_ip = remote_addr.ip();
     let peer_port = remote_addr.port();
@@ -216,12 +616,80 @@ fn handle_response(
     println!("{}", content);
     logger.log(content);
 
-    save_chunk(data);
+    save_chunk(&data.content, data.chunk_id, &chunk);
 }
 
-fn save_chunk(data: ResponseInfo) {
+fn save_chunk(content: &ContentId, chunk_id: ChunkId, chunk: &[u8]) {
     let mut file =
-        File::create(format!("chunk{}.m4s", data.chunk_id)).expect("Failed to create chunk file");
-    file.write(&data.chunk)
+        File::create(chunk_file_name(content, chunk_id)).expect("Failed to create chunk file");
+    file.write_all(chunk)
         .expect("Failed to write data to chunk file");
 }
+
+/// `chunk<id>.m4s`, prefixed with the content name unless it is the default content.
+fn chunk_file_name(content: &ContentId, chunk_id: ChunkId) -> String {
+    if content.is_default() {
+        format!("chunk{}.m4s", chunk_id)
+    } else {
+        format!(
+            "{}_chunk{}.m4s",
+            content.as_str().replace('/', "_"),
+            chunk_id
+        )
+    }
+}
+
+/// Directory holding one chunk directory store per seeding run, named after the port the
+/// run serves on.
+const SEED_DIRECTORY: &str = "seed";
+
+/// Turns the client into a peer serving the chunks it received, so that every viewer adds
+/// a provider to the swarm. The chunks are linked into a fresh directory under
+/// `SEED_DIRECTORY` and served from there rather than from memory, so they are read from
+/// disk as requested and the peer can reload them. The peer joins the swarm through the peer
+/// the client was started with and announces its chunks the same way the client found them.
+fn seed(config: &ClientConfig, udp_socket: UdpSocket, received: Vec<ChunkId>) {
+    let local_address = udp_socket
+        .local_addr()
+        .expect("Failed to get local address");
+    // Only the chunks received in this run are served, whatever earlier runs left behind.
+    let seed_directory = Path::new(SEED_DIRECTORY).join(local_address.port().to_string());
+    match fs::remove_dir_all(&seed_directory) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            panic!("Failed to clear {}: {}", seed_directory.display(), err)
+        }
+        _ => {}
+    }
+    let content_directory = seed_directory.join(config.content.as_str());
+    fs::create_dir_all(&content_directory).expect("Failed to create seed directory");
+    for chunk_id in received {
+        let source = chunk_file_name(&config.content, chunk_id);
+        let destination = content_directory.join(format!("{}.m4s", chunk_id));
+        if fs::hard_link(&source, &destination).is_err() {
+            fs::copy(&source, &destination).expect("Failed to copy chunk file");
+        }
+    }
+    let store = DirectoryStore::open(&seed_directory).expect("Failed to open seed directory");
+
+    let mut peer_config =
+        PeerConfig::with_defaults(local_address, seed_directory.display().to_string());
+    peer_config.store = StoreKind::Directory;
+    peer_config.known_peers = vec![config.address];
+    peer_config.bootstrap_peers = vec![config.address];
+    peer_config.tracker = config.tracker_address;
+    peer_config.dht = config.dht;
+
+    udp_socket
+        .set_nonblocking(false)
+        .expect("Failed to set blocking mode");
+    println!(
+        "Seeding {} chunks on port {}",
+        store.list().len(),
+        local_address.port()
+    );
+    let peer = Peer::start(Arc::new(peer_config), Box::new(store), udp_socket)
+        .unwrap_or_else(|err| panic!("Failed to seed: {}", err));
+    peer.leave_on_signal()
+        .unwrap_or_else(|err| panic!("Failed to seed: {}", err));
+    peer.serve();
+}
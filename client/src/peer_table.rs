@@ -0,0 +1,209 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct PeerStats {
+    /// Latency between sending the Hello and receiving this peer's first ChunkInfo. Zero for
+    /// peers only known through the tracker or the DHT.
+    pub rtt: Duration,
+    /// Amount of requests to this peer that stalled and had to be retried elsewhere.
+    pub failures: u32,
+    /// Amount of chunks this peer contributed to that failed hash verification.
+    pub corrupt_chunks: u32,
+}
+
+/// A corrupt chunk weighs as much as this many stalled requests.
+const CORRUPT_CHUNK_WEIGHT: u32 = 10;
+
+/// Every peer that advertised chunks to the client, used to pick where each chunk is
+/// requested from.
+pub struct PeerTable {
+    hello_sent_at: Instant,
+    peers: HashMap<SocketAddr, PeerStats>,
+}
+
+impl PeerTable {
+    pub fn new() -> PeerTable {
+        PeerTable {
+            hello_sent_at: Instant::now(),
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn hello_sent(&mut self) {
+        self.hello_sent_at = Instant::now();
+    }
+
+    pub fn record_advertisement(&mut self, peer: SocketAddr) {
+        let rtt = self.hello_sent_at.elapsed();
+        self.peers.entry(peer).or_insert(PeerStats {
+            rtt,
+            failures: 0,
+            corrupt_chunks: 0,
+        });
+    }
+
+    /// Records a provider listed by the tracker or found in the DHT. Neither says anything
+    /// about latency, so such providers all start out equal.
+    pub fn record_listed_provider(&mut self, peer: SocketAddr) {
+        self.peers.entry(peer).or_insert(PeerStats {
+            rtt: Duration::ZERO,
+            failures: 0,
+            corrupt_chunks: 0,
+        });
+    }
+
+    pub fn record_failure(&mut self, peer: SocketAddr) {
+        if let Some(stats) = self.peers.get_mut(&peer) {
+            stats.failures += 1;
+        }
+    }
+
+    pub fn record_corrupt_chunk(&mut self, peer: SocketAddr) {
+        if let Some(stats) = self.peers.get_mut(&peer) {
+            stats.corrupt_chunks += 1;
+        }
+    }
+
+    /// Picks the provider with the lowest expected cost, weighing its RTT by the amount of
+    /// requests already outstanding on it (`load`) and by its past failures. `exclude` is only
+    /// honored if there is some other provider to choose.
+    pub fn best_provider(
+        &self,
+        providers: &[SocketAddr],
+        exclude: Option<SocketAddr>,
+        load: &HashMap<SocketAddr, usize>,
+    ) -> Option<SocketAddr> {
+        let candidates: Vec<SocketAddr> = providers
+            .iter()
+            .copied()
+            .filter(|&provider| Some(provider) != exclude)
+            .collect();
+        let candidates = if candidates.is_empty() {
+            providers
+        } else {
+            &candidates[..]
+        };
+
+        candidates
+            .iter()
+            .copied()
+            .min_by_key(|provider| self.cost(provider, load))
+    }
+
+    fn cost(&self, provider: &SocketAddr, load: &HashMap<SocketAddr, usize>) -> u128 {
+        let (rtt, failures) = match self.peers.get(provider) {
+            Some(stats) => (
+                stats.rtt,
+                stats.failures + CORRUPT_CHUNK_WEIGHT * stats.corrupt_chunks,
+            ),
+            None => (Duration::from_secs(1), 0),
+        };
+        let outstanding = load.get(provider).copied().unwrap_or(0) as u128;
+
+        // Sub-millisecond RTTs on a LAN would otherwise make every peer look free.
+        let rtt = rtt.as_micros().max(1_000);
+
+        rtt * (outstanding + 1) * (failures as u128 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn table(peers: &[(u16, u64, u32, u32)]) -> PeerTable {
+        let mut table = PeerTable::new();
+        for &(port, rtt_ms, failures, corrupt_chunks) in peers {
+            table.peers.insert(
+                address(port),
+                PeerStats {
+                    rtt: Duration::from_millis(rtt_ms),
+                    failures,
+                    corrupt_chunks,
+                },
+            );
+        }
+        table
+    }
+
+    #[test]
+    fn prefers_the_fastest_idle_provider() {
+        let table = table(&[(1, 20, 0, 0), (2, 10, 0, 0)]);
+
+        assert_eq!(
+            table.best_provider(&[address(1), address(2)], None, &HashMap::new()),
+            Some(address(2))
+        );
+    }
+
+    #[test]
+    fn outstanding_requests_make_a_provider_more_expensive() {
+        let table = table(&[(1, 20, 0, 0), (2, 10, 0, 0)]);
+        let load: HashMap<SocketAddr, usize> = vec![(address(2), 2)].into_iter().collect();
+
+        assert_eq!(
+            table.best_provider(&[address(1), address(2)], None, &load),
+            Some(address(1))
+        );
+    }
+
+    #[test]
+    fn failures_and_corrupt_chunks_make_a_provider_more_expensive() {
+        let failing = table(&[(1, 20, 0, 0), (2, 10, 2, 0)]);
+        assert_eq!(
+            failing.best_provider(&[address(1), address(2)], None, &HashMap::new()),
+            Some(address(1))
+        );
+
+        // One corrupt chunk weighs more than nine stalled requests.
+        let corrupt = table(&[(1, 10, 9, 0), (2, 10, 0, 1)]);
+        assert_eq!(
+            corrupt.best_provider(&[address(1), address(2)], None, &HashMap::new()),
+            Some(address(1))
+        );
+    }
+
+    #[test]
+    fn sub_millisecond_rtts_count_as_one_millisecond() {
+        let table = table(&[(1, 0, 0, 0), (2, 1, 0, 0)]);
+        let load: HashMap<SocketAddr, usize> = vec![(address(1), 1)].into_iter().collect();
+
+        assert_eq!(
+            table.best_provider(&[address(1), address(2)], None, &load),
+            Some(address(2))
+        );
+    }
+
+    #[test]
+    fn excluded_provider_is_skipped_unless_it_is_the_only_one() {
+        let table = table(&[(1, 20, 0, 0), (2, 10, 0, 0)]);
+
+        assert_eq!(
+            table.best_provider(&[address(1), address(2)], Some(address(2)), &HashMap::new()),
+            Some(address(1))
+        );
+        assert_eq!(
+            table.best_provider(&[address(2)], Some(address(2)), &HashMap::new()),
+            Some(address(2))
+        );
+        assert_eq!(table.best_provider(&[], None, &HashMap::new()), None);
+    }
+
+    #[test]
+    fn unknown_providers_cost_a_second() {
+        let table = table(&[(1, 900, 0, 0)]);
+
+        assert_eq!(
+            table.best_provider(&[address(1), address(2)], None, &HashMap::new()),
+            Some(address(1))
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use common::{ChunkId, ContentId, LookupInfo, Message, NodeId, NodesInfo};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Queries in flight at once for a single chunk.
+const ALPHA: usize = 3;
+/// Queries in flight at once over all the chunks, so that asking for many chunks does not
+/// burst thousands of datagrams.
+const MAX_QUERIES_IN_FLIGHT: usize = 256;
+/// Amount of closest nodes a lookup converges on.
+const SHORTLIST_SIZE: usize = 8;
+/// How long a node has to answer before the lookup moves on without it.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Looks up the providers of every chunk in the DHT, all lookups running side by side. Each
+/// one starts from the peer the client was given and walks toward the nodes closest to the
+/// chunk's key, which keep its provider records, in O(log n) hops.
+pub struct ProviderSearch {
+    lookups: HashMap<NodeId, Lookup>,
+}
+
+struct Lookup {
+    chunk_id: ChunkId,
+    /// Candidates, closest to the key first. The seed peer, whose ID is unknown, counts as the
+    /// farthest.
+    shortlist: Vec<(Option<NodeId>, SocketAddr)>,
+    queried: HashSet<SocketAddr>,
+    in_flight: HashMap<SocketAddr, Instant>,
+    finished: bool,
+}
+
+impl ProviderSearch {
+    pub fn new(content: &ContentId, chunks: &[ChunkId], seed: SocketAddr) -> ProviderSearch {
+        let lookups = chunks
+            .iter()
+            .map(|&chunk_id| {
+                let lookup = Lookup {
+                    chunk_id,
+                    shortlist: vec![(None, seed)],
+                    queried: HashSet::new(),
+                    in_flight: HashMap::new(),
+                    finished: false,
+                };
+                (common::chunk_key(content, chunk_id), lookup)
+            })
+            .collect();
+
+        ProviderSearch { lookups }
+    }
+
+    /// Gives up on the queries that timed out and sends the next ones. Lookups with nobody
+    /// left to ask finish without providers.
+    pub fn step(&mut self, udp_socket: &UdpSocket) {
+        let mut in_flight: usize = self
+            .lookups
+            .values()
+            .map(|lookup| lookup.in_flight.len())
+            .sum();
+
+        for (key, lookup) in self.lookups.iter_mut().filter(|(_key, l)| !l.finished) {
+            let timed_out: Vec<SocketAddr> = lookup
+                .in_flight
+                .iter()
+                .filter(|(_address, sent_at)| sent_at.elapsed() > QUERY_TIMEOUT)
+                .map(|(address, _sent_at)| *address)
+                .collect();
+            for address in timed_out {
+                lookup.in_flight.remove(&address);
+                lookup.shortlist.retain(|(_id, known)| *known != address);
+                in_flight -= 1;
+            }
+
+            let candidates: Vec<SocketAddr> = lookup
+                .shortlist
+                .iter()
+                .take(SHORTLIST_SIZE)
+                .map(|(_id, address)| *address)
+                .filter(|address| !lookup.queried.contains(address))
+                .collect();
+            for address in candidates {
+                if lookup.in_flight.len() >= ALPHA || in_flight >= MAX_QUERIES_IN_FLIGHT {
+                    break;
+                }
+
+                let query = Message::FindProviders(LookupInfo::find_providers(None, *key));
+                crate::send_to(udp_socket, &query.serialize(), &address);
+                lookup.queried.insert(address);
+                lookup.in_flight.insert(address, Instant::now());
+                in_flight += 1;
+            }
+
+            // A lookup held back by the queries in flight over all chunks still has candidates,
+            // and goes on once some of those queries are answered.
+            let has_candidates = lookup
+                .shortlist
+                .iter()
+                .take(SHORTLIST_SIZE)
+                .any(|(_id, address)| !lookup.queried.contains(address));
+            if lookup.in_flight.is_empty() && !has_candidates {
+                println!("No provider found in the DHT for chunk {}", lookup.chunk_id);
+                lookup.finished = true;
+            }
+        }
+    }
+
+    /// Takes in a Nodes message from `from`, returning the chunk it answered for and the
+    /// providers it listed, if any.
+    pub fn handle_nodes(
+        &mut self,
+        data: NodesInfo,
+        from: SocketAddr,
+    ) -> Option<(ChunkId, Vec<SocketAddr>)> {
+        let key = data.target;
+        let lookup = self.lookups.get_mut(&key)?;
+        if lookup.finished || lookup.in_flight.remove(&from).is_none() {
+            return None;
+        }
+
+        for node in data.nodes {
+            let known = lookup
+                .shortlist
+                .iter()
+                .any(|(_id, address)| *address == node.address);
+            if !known && !lookup.queried.contains(&node.address) {
+                lookup.shortlist.push((Some(node.id), node.address));
+            }
+        }
+        lookup
+            .shortlist
+            .sort_by_key(|(id, _address)| id.map_or(u64::MAX, |id| common::distance(id, key)));
+
+        if data.providers.is_empty() {
+            return None;
+        }
+
+        // Answers still to come no longer matter, and must not hold back other lookups.
+        lookup.in_flight.clear();
+        lookup.finished = true;
+        Some((lookup.chunk_id, data.providers))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.lookups.values().all(|lookup| lookup.finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::Contact;
+    use std::thread;
+
+    fn socket() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        socket
+    }
+
+    fn was_queried(node: &UdpSocket) -> bool {
+        let mut buffer = [0; 1500];
+        node.recv_from(&mut buffer).is_ok()
+    }
+
+    fn nodes(key: NodeId, providers: Vec<SocketAddr>, nodes: Vec<Contact>) -> NodesInfo {
+        NodesInfo::from_nodes(Some(1), key, providers, nodes)
+    }
+
+    fn key() -> NodeId {
+        common::chunk_key(&ContentId::default(), 7)
+    }
+
+    #[test]
+    fn lookup_finishes_once_providers_are_listed() {
+        let client = socket();
+        let seed = socket();
+        let seed_address = seed.local_addr().unwrap();
+        let provider = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let mut search = ProviderSearch::new(&ContentId::default(), &[7], seed_address);
+
+        search.step(&client);
+        assert!(was_queried(&seed));
+
+        let found = search.handle_nodes(nodes(key(), vec![provider], Vec::new()), seed_address);
+        assert_eq!(found, Some((7, vec![provider])));
+        assert!(search.is_finished());
+    }
+
+    #[test]
+    fn replies_from_nodes_not_queried_are_ignored() {
+        let client = socket();
+        let seed = socket();
+        let stranger = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let mut search =
+            ProviderSearch::new(&ContentId::default(), &[7], seed.local_addr().unwrap());
+
+        search.step(&client);
+
+        let found = search.handle_nodes(nodes(key(), vec![stranger], Vec::new()), stranger);
+        assert_eq!(found, None);
+        assert!(!search.is_finished());
+    }
+
+    #[test]
+    fn lookup_moves_on_to_the_nodes_it_learns_of() {
+        let client = socket();
+        let seed = socket();
+        let closer = socket();
+        let seed_address = seed.local_addr().unwrap();
+        let closer_contact = Contact {
+            id: key() ^ 1,
+            address: closer.local_addr().unwrap(),
+        };
+        let mut search = ProviderSearch::new(&ContentId::default(), &[7], seed_address);
+
+        search.step(&client);
+        let found =
+            search.handle_nodes(nodes(key(), Vec::new(), vec![closer_contact]), seed_address);
+        assert_eq!(found, None);
+
+        search.step(&client);
+        assert!(was_queried(&closer));
+        assert!(!search.is_finished());
+    }
+
+    #[test]
+    fn lookups_held_back_by_the_query_budget_wait_their_turn() {
+        let client = socket();
+        let seed = socket();
+        let seed_address = seed.local_addr().unwrap();
+        let chunks: Vec<ChunkId> = (0..MAX_QUERIES_IN_FLIGHT as ChunkId + 50).collect();
+        let mut search = ProviderSearch::new(&ContentId::default(), &chunks, seed_address);
+        let provider = SocketAddr::from(([127, 0, 0, 1], 4000));
+
+        search.step(&client);
+        let queried: Vec<NodeId> = search
+            .lookups
+            .iter()
+            .filter(|(_key, lookup)| !lookup.in_flight.is_empty())
+            .map(|(key, _lookup)| *key)
+            .collect();
+        assert_eq!(queried.len(), MAX_QUERIES_IN_FLIGHT);
+        assert!(search.lookups.values().all(|lookup| !lookup.finished));
+
+        for key in queried {
+            let found = search.handle_nodes(nodes(key, vec![provider], Vec::new()), seed_address);
+            assert!(found.is_some());
+        }
+        assert!(search
+            .lookups
+            .values()
+            .all(|lookup| !lookup.finished || lookup.in_flight.is_empty()));
+
+        search.step(&client);
+        assert!(search
+            .lookups
+            .values()
+            .all(|lookup| lookup.finished || lookup.in_flight.len() == 1));
+        assert!(!search.is_finished());
+    }
+
+    #[test]
+    fn lookup_gives_up_when_nobody_answers() {
+        let client = socket();
+        let seed = socket();
+        let mut search =
+            ProviderSearch::new(&ContentId::default(), &[7], seed.local_addr().unwrap());
+
+        search.step(&client);
+        assert!(!search.is_finished());
+
+        thread::sleep(QUERY_TIMEOUT + Duration::from_millis(50));
+        search.step(&client);
+        assert!(search.is_finished());
+    }
+}
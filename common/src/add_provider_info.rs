@@ -0,0 +1,71 @@
+use crate::byte_utils;
+use crate::dht::{self, NodeId};
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+
+/// Keeps an AddProvider within one datagram.
+pub const MAX_KEYS_PER_ANNOUNCEMENT: usize = 4 * 1024;
+
+/// Tells a DHT node that the sender provides the chunks whose keys are listed, so that it
+/// records the sender's address as a provider of each of them.
+pub struct AddProviderInfo {
+    pub message_type: MessageType,
+    pub sender: Option<NodeId>,
+    pub keys: Vec<NodeId>,
+}
+
+impl AddProviderInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<AddProviderInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (_version, message_type) = MessageType::parse_header(message)?;
+        let (sender, sender_length) = dht::parse_sender(&message[2..])?;
+        let count_start = 2 + sender_length;
+
+        byte_utils::require(message, count_start + 2)?;
+        let amount_of_keys =
+            byte_utils::u16_from_u8_array(&message[count_start..count_start + 2]) as usize;
+        let keys_start = count_start + 2;
+        let expected_length = keys_start + amount_of_keys * 8;
+        if message.len() != expected_length {
+            return Err(ProtocolError::LengthMismatch {
+                declared: expected_length,
+                actual: message.len(),
+            });
+        }
+
+        let keys = message[keys_start..]
+            .chunks(8)
+            .map(byte_utils::u64_from_u8_array)
+            .collect();
+
+        Ok(AddProviderInfo {
+            message_type,
+            sender,
+            keys,
+        })
+    }
+
+    /// Builds a message providing `keys`. Only the first `MAX_KEYS_PER_ANNOUNCEMENT` are kept.
+    pub fn from_keys(sender: Option<NodeId>, mut keys: Vec<NodeId>) -> AddProviderInfo {
+        keys.truncate(MAX_KEYS_PER_ANNOUNCEMENT);
+
+        AddProviderInfo {
+            message_type: MessageType::AddProvider,
+            sender,
+            keys,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(ProtocolVersion::V1).iter());
+        data.append(&mut dht::serialize_sender(self.sender));
+        data.extend((self.keys.len() as u16).to_be_bytes().iter());
+        for key in &self.keys {
+            data.extend(key.to_be_bytes().iter());
+        }
+
+        data
+    }
+}
@@ -0,0 +1,69 @@
+use crate::byte_utils;
+use crate::protocol_error::ProtocolError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const IPV4_FAMILY: u8 = 4;
+const IPV6_FAMILY: u8 = 6;
+
+/// Encodes `address` as an address-family tag followed by the IP octets and the port.
+pub(crate) fn serialize_address(address: &SocketAddr) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::new();
+
+    match address.ip() {
+        IpAddr::V4(ip) => {
+            data.push(IPV4_FAMILY);
+            data.extend(ip.octets().iter());
+        }
+        IpAddr::V6(ip) => {
+            data.push(IPV6_FAMILY);
+            data.extend(ip.octets().iter());
+        }
+    }
+    data.extend(address.port().to_be_bytes().iter());
+
+    data
+}
+
+/// Decodes an address written by `serialize_address`, returning it along with the amount
+/// of bytes it took.
+pub(crate) fn parse_address(message: &[u8]) -> Result<(SocketAddr, usize), ProtocolError> {
+    byte_utils::require(message, 1)?;
+
+    let (ip, ip_length) = match message[0] {
+        IPV4_FAMILY => {
+            byte_utils::require(message, 1 + 4 + 2)?;
+            let mut octets = [0; 4];
+            octets.copy_from_slice(&message[1..5]);
+            (IpAddr::V4(Ipv4Addr::from(octets)), 4)
+        }
+        IPV6_FAMILY => {
+            byte_utils::require(message, 1 + 16 + 2)?;
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&message[1..17]);
+            (IpAddr::V6(Ipv6Addr::from(octets)), 16)
+        }
+        _ => return Err(ProtocolError::BadAddress),
+    };
+
+    let port_start = 1 + ip_length;
+    let port = byte_utils::u16_from_u8_array(&message[port_start..port_start + 2]);
+
+    Ok((SocketAddr::new(ip, port), port_start + 2))
+}
+
+/// Turns IPv4-mapped IPv6 addresses, as reported by dual-stack sockets, back into plain IPv4
+/// addresses.
+pub fn canonical_address(address: SocketAddr) -> SocketAddr {
+    SocketAddr::new(address.ip().to_canonical(), address.port())
+}
+
+/// Returns the form of `target` that a socket bound to `local` can send to: IPv6 sockets
+/// reach IPv4 hosts through IPv4-mapped addresses.
+pub fn reachable_address(local: &SocketAddr, target: SocketAddr) -> SocketAddr {
+    match (local.ip(), target.ip()) {
+        (IpAddr::V6(_), IpAddr::V4(ip)) => {
+            SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), target.port())
+        }
+        _ => target,
+    }
+}
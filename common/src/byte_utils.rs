@@ -1,3 +1,40 @@
+use crate::protocol_error::ProtocolError;
+
 pub fn u16_from_u8_array(u8_array: &[u8]) -> u16 {
-    ((u8_array[0] as u16) << 8) + ((u8_array[1] as u16) << 0)
+    ((u8_array[0] as u16) << 8) + (u8_array[1] as u16)
+}
+
+pub fn u32_from_u8_array(u8_array: &[u8]) -> u32 {
+    ((u8_array[0] as u32) << 24)
+        + ((u8_array[1] as u32) << 16)
+        + ((u8_array[2] as u32) << 8)
+        + (u8_array[3] as u32)
+}
+
+pub fn u64_from_u8_array(u8_array: &[u8]) -> u64 {
+    ((u32_from_u8_array(&u8_array[0..4]) as u64) << 32)
+        + (u32_from_u8_array(&u8_array[4..8]) as u64)
+}
+
+/// Returns the part of `message` that was actually filled by the socket, so that decoders
+/// never look past `bytes_read` nor past the end of the buffer. A `bytes_read` larger than
+/// the buffer is a caller error rather than a short message.
+pub(crate) fn received(message: &[u8], bytes_read: usize) -> Result<&[u8], ProtocolError> {
+    message
+        .get(..bytes_read)
+        .ok_or(ProtocolError::LengthMismatch {
+            declared: bytes_read,
+            actual: message.len(),
+        })
+}
+
+pub(crate) fn require(message: &[u8], expected: usize) -> Result<(), ProtocolError> {
+    if message.len() < expected {
+        return Err(ProtocolError::Truncated {
+            expected,
+            got: message.len(),
+        });
+    }
+
+    Ok(())
 }
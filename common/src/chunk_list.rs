@@ -1,88 +1,328 @@
 use crate::byte_utils;
+use crate::content_id::ContentId;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use crate::ChunkId;
 
 pub struct ChunkListMessage {
-    pub message_type: u16,
+    pub message_type: MessageType,
+    pub content: ContentId,
     pub chunk_list: ChunkList,
 }
 
 impl ChunkListMessage {
-    pub fn new(message: &[u8], bytes_read: usize) -> Result<ChunkListMessage, &'static str> {
-        if bytes_read < 4 {
-            return Err(
-                "Less than 4 bytes read for message that should contain at least 4 bytes.",
-            );
-        }
-
-        let message_type = byte_utils::u16_from_u8_array(&message[0..2]);
-        let chunk_list = ChunkList::new(&message[2..], bytes_read - 2)?;
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<ChunkListMessage, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (version, message_type) = MessageType::parse_header(message)?;
+        let (content, content_length) = ContentId::parse(&message[2..])?;
+        let list_start = 2 + content_length;
+        let chunk_list =
+            ChunkList::new(&message[list_start..], message.len() - list_start, version)?;
         Ok(ChunkListMessage {
             message_type,
+            content,
             chunk_list,
         })
     }
 
-    pub fn from_chunks(message_type: u16, chunks: Vec<u16>) -> ChunkListMessage {
+    pub fn from_chunks(
+        message_type: MessageType,
+        content: ContentId,
+        chunks: Vec<ChunkId>,
+    ) -> ChunkListMessage {
         ChunkListMessage {
             message_type,
-            chunk_list: ChunkList {
-                amount_of_chunks: chunks.len() as u16,
-                chunks,
-            },
+            content,
+            chunk_list: ChunkList::from_chunks(chunks),
         }
     }
 
     pub fn serialize(&self) -> Vec<u8> {
+        let version = self.chunk_list.version();
+
         let mut data: Vec<u8> = Vec::new();
-        data.extend(self.message_type.to_be_bytes().iter());
-        data.append(&mut self.chunk_list.serialize());
+        data.extend(self.message_type.header(version).iter());
+        data.append(&mut self.content.serialize());
+        data.append(&mut self.chunk_list.serialize(version));
 
         data
     }
 }
 
+/// Largest amount of chunks a decoded list may hold. Ranges and bitfields describe many
+/// chunks in a few bytes, so without a cap a tiny datagram could expand into gigabytes.
+/// Senders must split longer lists across several messages.
+pub const MAX_CHUNK_LIST_LENGTH: usize = 1 << 20;
+
+/// Layout of a version 2 chunk list, written as a one-byte tag before it. Version 1 lists are
+/// always explicit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkListEncoding {
+    /// A count followed by every chunk ID.
+    Explicit = 0,
+    /// A count of ranges followed by the first and last chunk ID of each range.
+    Ranges = 1,
+    /// The first chunk ID and an amount of bits, followed by the bits: bit `i`, starting from
+    /// the most significant bit of the first byte, stands for chunk `first + i`.
+    Bitfield = 2,
+}
+
 #[derive(Clone)]
 pub struct ChunkList {
-    pub amount_of_chunks: u16,
-    pub chunks: Vec<u16>,
+    pub amount_of_chunks: u32,
+    pub chunks: Vec<ChunkId>,
 }
 
 impl ChunkList {
-    pub fn new(message: &[u8], bytes_read: usize) -> Result<ChunkList, &'static str> {
-        if bytes_read < 2 {
-            return Err(
-                "Foram lidos menos de 2 bytes para uma mensagem que deve conter no mínimo 2 bytes",
-            );
-        }
+    /// Decodes a chunk list written by `serialize` with the same `version`.
+    pub fn new(
+        message: &[u8],
+        bytes_read: usize,
+        version: ProtocolVersion,
+    ) -> Result<ChunkList, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+
+        let chunks = match version {
+            ProtocolVersion::V1 => ChunkList::parse_explicit(message, version)?,
+            ProtocolVersion::V2 => {
+                byte_utils::require(message, 1)?;
+                let body = &message[1..];
+
+                match message[0] {
+                    0 => ChunkList::parse_explicit(body, version)?,
+                    1 => ChunkList::parse_ranges(body)?,
+                    2 => ChunkList::parse_bitfield(body)?,
+                    tag => return Err(ProtocolError::UnknownEncoding(tag)),
+                }
+            }
+        };
+
+        Ok(ChunkList::from_chunks(chunks))
+    }
+
+    fn parse_explicit(
+        message: &[u8],
+        version: ProtocolVersion,
+    ) -> Result<Vec<ChunkId>, ProtocolError> {
+        let id_size = version.id_size();
+        byte_utils::require(message, id_size)?;
+
+        let amount_of_chunks = version.parse_id(&message[0..id_size]);
+        let slice_end = (amount_of_chunks as usize)
+            .saturating_mul(id_size)
+            .saturating_add(id_size);
+        byte_utils::require(message, slice_end)?;
+        let raw_bytes = &message[id_size..slice_end];
+
+        Ok(raw_bytes
+            .chunks_exact(id_size)
+            .map(|id| version.parse_id(id))
+            .collect())
+    }
+
+    fn parse_ranges(message: &[u8]) -> Result<Vec<ChunkId>, ProtocolError> {
+        byte_utils::require(message, 4)?;
 
-        let amount_of_chunks = byte_utils::u16_from_u8_array(&message[0..2]);
-        let slice_end = (amount_of_chunks * 2 + 2) as usize;
-        let raw_bytes = &message[2..slice_end];
+        let amount_of_ranges = byte_utils::u32_from_u8_array(&message[0..4]) as usize;
+        let slice_end = amount_of_ranges.saturating_mul(8).saturating_add(4);
+        byte_utils::require(message, slice_end)?;
 
         let mut chunks = Vec::new();
+        for range in message[4..slice_end].chunks_exact(8) {
+            let start = byte_utils::u32_from_u8_array(&range[0..4]);
+            let end = byte_utils::u32_from_u8_array(&range[4..8]);
+            if start > end {
+                return Err(ProtocolError::InvalidRange { start, end });
+            }
+
+            let length = (end - start) as usize + 1;
+            if chunks.len() + length > MAX_CHUNK_LIST_LENGTH {
+                return Err(ProtocolError::TooManyChunks {
+                    max: MAX_CHUNK_LIST_LENGTH,
+                });
+            }
+            chunks.extend(start..=end);
+        }
+
+        Ok(chunks)
+    }
+
+    fn parse_bitfield(message: &[u8]) -> Result<Vec<ChunkId>, ProtocolError> {
+        byte_utils::require(message, 8)?;
 
-        for i in (0..raw_bytes.len()).step_by(2) {
-            let val = &raw_bytes[i..i + 2];
-            let val = byte_utils::u16_from_u8_array(val);
-            chunks.push(val);
+        let first = byte_utils::u32_from_u8_array(&message[0..4]);
+        let amount_of_bits = byte_utils::u32_from_u8_array(&message[4..8]);
+        if amount_of_bits as usize > MAX_CHUNK_LIST_LENGTH {
+            return Err(ProtocolError::TooManyChunks {
+                max: MAX_CHUNK_LIST_LENGTH,
+            });
         }
+        if amount_of_bits > 0 && first.checked_add(amount_of_bits - 1).is_none() {
+            return Err(ProtocolError::InvalidRange {
+                start: first,
+                end: u32::MAX,
+            });
+        }
+
+        let slice_end = 8 + (amount_of_bits as usize).div_ceil(8);
+        byte_utils::require(message, slice_end)?;
+        let bits = &message[8..slice_end];
+
+        Ok((0..amount_of_bits)
+            .filter(|&bit| bits[bit as usize / 8] & (0x80 >> (bit % 8)) != 0)
+            .map(|bit| first + bit)
+            .collect())
+    }
 
-        Ok(ChunkList {
-            amount_of_chunks,
+    pub fn from_chunks(chunks: Vec<ChunkId>) -> ChunkList {
+        ChunkList {
+            amount_of_chunks: chunks.len() as u32,
             chunks,
-        })
+        }
     }
 
-    pub fn serialize(&self) -> Vec<u8> {
+    /// The oldest protocol version able to carry this list, or version 2 if one of its
+    /// compact encodings is smaller than the version 1 list.
+    pub fn version(&self) -> ProtocolVersion {
+        self.encoding().0
+    }
+
+    /// The smallest way to write this list, preferring version 1 on ties.
+    pub fn encoding(&self) -> (ProtocolVersion, ChunkListEncoding) {
+        ChunkList::smallest(self.candidate_encodings())
+    }
+
+    /// Every way this list can be written, along with its size in bytes.
+    fn candidate_encodings(&self) -> Vec<(usize, ProtocolVersion, ChunkListEncoding)> {
+        let mut candidates = Vec::new();
+
+        let largest_id = self.chunks.iter().copied().max().unwrap_or(0);
+        let fits_version_1 = ProtocolVersion::for_value(largest_id as usize)
+            .max(ProtocolVersion::for_value(self.amount_of_chunks as usize))
+            == ProtocolVersion::V1;
+        if fits_version_1 {
+            candidates.push((
+                2 + 2 * self.chunks.len(),
+                ProtocolVersion::V1,
+                ChunkListEncoding::Explicit,
+            ));
+        }
+
+        candidates.push((
+            1 + 4 + 4 * self.chunks.len(),
+            ProtocolVersion::V2,
+            ChunkListEncoding::Explicit,
+        ));
+
+        // Ranges and bitfields describe sets, so they are only used when decoding them gives
+        // back the exact same list.
+        let strictly_increasing = self.chunks.windows(2).all(|pair| pair[0] < pair[1]);
+        if strictly_increasing && !self.chunks.is_empty() {
+            candidates.push((
+                1 + 4 + 8 * self.ranges().len(),
+                ProtocolVersion::V2,
+                ChunkListEncoding::Ranges,
+            ));
+
+            // Decoders refuse bitfields with more bits than the cap, even mostly empty ones.
+            let span = (largest_id - self.chunks[0]) as usize + 1;
+            if span <= MAX_CHUNK_LIST_LENGTH {
+                candidates.push((
+                    1 + 8 + span.div_ceil(8),
+                    ProtocolVersion::V2,
+                    ChunkListEncoding::Bitfield,
+                ));
+            }
+        }
+
+        candidates
+    }
+
+    fn smallest(
+        candidates: Vec<(usize, ProtocolVersion, ChunkListEncoding)>,
+    ) -> (ProtocolVersion, ChunkListEncoding) {
+        candidates
+            .into_iter()
+            .min_by_key(|(size, _version, _encoding)| *size)
+            .map(|(_size, version, encoding)| (version, encoding))
+            .unwrap_or((ProtocolVersion::V1, ChunkListEncoding::Explicit))
+    }
+
+    /// Runs of consecutive chunk IDs, as inclusive (first, last) pairs.
+    fn ranges(&self) -> Vec<(ChunkId, ChunkId)> {
+        let mut ranges: Vec<(ChunkId, ChunkId)> = Vec::new();
+
+        for &chunk in &self.chunks {
+            match ranges.last_mut() {
+                Some((_first, last)) if last.checked_add(1) == Some(chunk) => *last = chunk,
+                _ => ranges.push((chunk, chunk)),
+            }
+        }
+
+        ranges
+    }
+
+    /// Writes the list for a message of the given `version`, which must not be older than
+    /// `self.version()`. Version 2 lists use their smallest encoding.
+    ///
+    /// Panics if the list holds more than `MAX_CHUNK_LIST_LENGTH` chunks, since no peer would
+    /// accept it.
+    pub fn serialize(&self, version: ProtocolVersion) -> Vec<u8> {
+        assert!(
+            self.chunks.len() <= MAX_CHUNK_LIST_LENGTH,
+            "Chunk list of {} chunks is longer than {}",
+            self.chunks.len(),
+            MAX_CHUNK_LIST_LENGTH
+        );
+
+        let encoding = match version {
+            ProtocolVersion::V1 => ChunkListEncoding::Explicit,
+            ProtocolVersion::V2 => self.smallest_version_2_encoding(),
+        };
+
         let mut data: Vec<u8> = Vec::new();
+        if version == ProtocolVersion::V2 {
+            data.push(encoding as u8);
+        }
 
-        data.extend(self.amount_of_chunks.to_be_bytes().iter());
-        let chunks = &self.chunks;
-        let mut chunks = chunks
-            .iter()
-            .flat_map(|chunk| Vec::from(chunk.to_be_bytes()))
-            .collect::<Vec<_>>();
+        match encoding {
+            ChunkListEncoding::Explicit => {
+                data.append(&mut version.serialize_id(self.amount_of_chunks));
+                for &chunk in &self.chunks {
+                    data.append(&mut version.serialize_id(chunk));
+                }
+            }
+            ChunkListEncoding::Ranges => {
+                let ranges = self.ranges();
+                data.extend((ranges.len() as u32).to_be_bytes().iter());
+                for (first, last) in ranges {
+                    data.extend(first.to_be_bytes().iter());
+                    data.extend(last.to_be_bytes().iter());
+                }
+            }
+            ChunkListEncoding::Bitfield => {
+                let first = self.chunks[0];
+                let amount_of_bits = self.chunks[self.chunks.len() - 1] - first + 1;
+                let mut bits = vec![0u8; (amount_of_bits as usize).div_ceil(8)];
+                for &chunk in &self.chunks {
+                    let bit = (chunk - first) as usize;
+                    bits[bit / 8] |= 0x80 >> (bit % 8);
+                }
+
+                data.extend(first.to_be_bytes().iter());
+                data.extend(amount_of_bits.to_be_bytes().iter());
+                data.append(&mut bits);
+            }
+        }
 
-        data.append(&mut chunks);
         data
     }
+
+    fn smallest_version_2_encoding(&self) -> ChunkListEncoding {
+        let mut candidates = self.candidate_encodings();
+        candidates.retain(|(_size, version, _encoding)| *version == ProtocolVersion::V2);
+
+        ChunkList::smallest(candidates).1
+    }
 }
@@ -0,0 +1,68 @@
+use crate::byte_utils;
+use crate::protocol_error::ProtocolError;
+use std::fmt;
+
+/// Longest content name, in bytes, that fits the one-byte length prefix.
+pub const MAX_CONTENT_ID_LENGTH: usize = u8::MAX as usize;
+
+/// Names the video a chunk belongs to, so a swarm can distribute several videos at once. The
+/// empty name is the default content, used when none is given.
+///
+/// Names may have several `/`-separated segments, such as `sintel/720p`, which stores map to
+/// subdirectories. Segments cannot be empty, `.` or `..`, so a name never escapes the store.
+///
+/// On the wire it is a one-byte length followed by the UTF-8 name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentId(String);
+
+impl ContentId {
+    pub fn new(name: &str) -> Result<ContentId, ProtocolError> {
+        let bad_segment = |segment: &str| segment.is_empty() || segment == "." || segment == "..";
+        if name.len() > MAX_CONTENT_ID_LENGTH
+            || (!name.is_empty() && name.split('/').any(bad_segment))
+        {
+            return Err(ProtocolError::InvalidContentId);
+        }
+
+        Ok(ContentId(name.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::with_capacity(1 + self.0.len());
+        data.push(self.0.len() as u8);
+        data.extend(self.0.as_bytes());
+
+        data
+    }
+
+    /// Decodes a content ID written by `serialize`, returning it along with the amount of
+    /// bytes it took.
+    pub(crate) fn parse(message: &[u8]) -> Result<(ContentId, usize), ProtocolError> {
+        byte_utils::require(message, 1)?;
+
+        let end = 1 + message[0] as usize;
+        byte_utils::require(message, end)?;
+        let name =
+            std::str::from_utf8(&message[1..end]).map_err(|_| ProtocolError::InvalidContentId)?;
+
+        Ok((ContentId::new(name)?, end))
+    }
+}
+
+impl fmt::Display for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_default() {
+            write!(f, "(default)")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::address_utils;
+use crate::byte_utils;
+use crate::content_id::ContentId;
+use crate::protocol_error::ProtocolError;
+use crate::ChunkId;
+use std::net::SocketAddr;
+
+/// Identifies a peer taking part in the DHT. Chunks are mapped into the same space by
+/// `chunk_key`, and the provider records of a chunk are kept by the peers whose IDs are
+/// closest to its key.
+pub type NodeId = u64;
+
+/// Kademlia's XOR metric.
+pub fn distance(a: NodeId, b: NodeId) -> u64 {
+    a ^ b
+}
+
+/// Position of a chunk in the DHT: the 64-bit FNV-1a hash of the content name, a separator
+/// and the big-endian chunk ID. Every node and client must agree on it, so it cannot use the
+/// randomly keyed hashers of the standard library.
+pub fn chunk_key(content: &ContentId, chunk_id: ChunkId) -> NodeId {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    content
+        .as_str()
+        .as_bytes()
+        .iter()
+        .chain(std::iter::once(&0))
+        .chain(chunk_id.to_be_bytes().iter())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+/// A DHT node and the address it is reached at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Contact {
+    pub id: NodeId,
+    pub address: SocketAddr,
+}
+
+impl Contact {
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.id.to_be_bytes().iter());
+        data.append(&mut address_utils::serialize_address(&self.address));
+
+        data
+    }
+
+    /// Decodes a contact written by `serialize`, returning it along with the amount of bytes
+    /// it took.
+    pub(crate) fn parse(message: &[u8]) -> Result<(Contact, usize), ProtocolError> {
+        byte_utils::require(message, 8)?;
+        let id = byte_utils::u64_from_u8_array(&message[0..8]);
+        let (address, address_length) = address_utils::parse_address(&message[8..])?;
+
+        Ok((Contact { id, address }, 8 + address_length))
+    }
+}
+
+/// Writes the ID of the node sending a DHT message: a flag byte, followed by the ID when the
+/// sender is a node. Clients only look up providers and do not take part in the DHT.
+pub(crate) fn serialize_sender(sender: Option<NodeId>) -> Vec<u8> {
+    match sender {
+        Some(id) => {
+            let mut data = vec![1];
+            data.extend(id.to_be_bytes().iter());
+            data
+        }
+        None => vec![0],
+    }
+}
+
+/// Reads the sender written by `serialize_sender`, returning it along with the amount of
+/// bytes it took.
+pub(crate) fn parse_sender(message: &[u8]) -> Result<(Option<NodeId>, usize), ProtocolError> {
+    byte_utils::require(message, 1)?;
+
+    match message[0] {
+        0 => Ok((None, 1)),
+        _ => {
+            byte_utils::require(message, 9)?;
+            Ok((Some(byte_utils::u64_from_u8_array(&message[1..9])), 9))
+        }
+    }
+}
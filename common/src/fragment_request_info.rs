@@ -0,0 +1,66 @@
+use crate::byte_utils;
+use crate::chunk_list::ChunkList;
+use crate::content_id::ContentId;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use crate::ChunkId;
+
+/// Asks a peer to resend some fragments of a single chunk. The fragment indexes are carried
+/// with the same encoding as a list of chunks, and always fit in a u16.
+pub struct FragmentRequestInfo {
+    pub message_type: MessageType,
+    pub content: ContentId,
+    pub chunk_id: ChunkId,
+    pub fragments: ChunkList,
+}
+
+impl FragmentRequestInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<FragmentRequestInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (version, message_type) = MessageType::parse_header(message)?;
+        let (content, content_length) = ContentId::parse(&message[2..])?;
+        let id_start = 2 + content_length;
+        let list_start = id_start + version.id_size();
+
+        byte_utils::require(message, list_start)?;
+        let chunk_id = version.parse_id(&message[id_start..list_start]);
+        let fragments =
+            ChunkList::new(&message[list_start..], message.len() - list_start, version)?;
+
+        Ok(FragmentRequestInfo {
+            message_type,
+            content,
+            chunk_id,
+            fragments,
+        })
+    }
+
+    pub fn from_fragments(
+        content: ContentId,
+        chunk_id: ChunkId,
+        fragments: Vec<u16>,
+    ) -> FragmentRequestInfo {
+        let fragments = fragments.into_iter().map(u32::from).collect();
+
+        FragmentRequestInfo {
+            message_type: MessageType::GetFragments,
+            content,
+            chunk_id,
+            fragments: ChunkList::from_chunks(fragments),
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let version =
+            ProtocolVersion::for_value(self.chunk_id as usize).max(self.fragments.version());
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(version).iter());
+        data.append(&mut self.content.serialize());
+        data.append(&mut version.serialize_id(self.chunk_id));
+        data.append(&mut self.fragments.serialize(version));
+
+        data
+    }
+}
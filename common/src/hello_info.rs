@@ -0,0 +1,60 @@
+use crate::byte_utils;
+use crate::chunk_list::ChunkList;
+use crate::content_id::ContentId;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::ChunkId;
+
+/// Sent by the client to the first peer it contacts. `peer_ttl` is the TTL the client wants
+/// its query flooded with; 0 leaves the choice to the peer.
+pub struct HelloInfo {
+    pub message_type: MessageType,
+    pub content: ContentId,
+    pub peer_ttl: u16,
+    pub chunk_list: ChunkList,
+}
+
+impl HelloInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<HelloInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (version, message_type) = MessageType::parse_header(message)?;
+        let (content, content_length) = ContentId::parse(&message[2..])?;
+        let ttl_start = 2 + content_length;
+
+        byte_utils::require(message, ttl_start + 2)?;
+        let peer_ttl = byte_utils::u16_from_u8_array(&message[ttl_start..ttl_start + 2]);
+        let chunk_list = ChunkList::new(
+            &message[ttl_start + 2..],
+            message.len() - ttl_start - 2,
+            version,
+        )?;
+
+        Ok(HelloInfo {
+            message_type,
+            content,
+            peer_ttl,
+            chunk_list,
+        })
+    }
+
+    pub fn from_chunks(content: ContentId, peer_ttl: u16, chunks: Vec<ChunkId>) -> HelloInfo {
+        HelloInfo {
+            message_type: MessageType::Hello,
+            content,
+            peer_ttl,
+            chunk_list: ChunkList::from_chunks(chunks),
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let version = self.chunk_list.version();
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(version).iter());
+        data.append(&mut self.content.serialize());
+        data.extend(self.peer_ttl.to_be_bytes().iter());
+        data.append(&mut self.chunk_list.serialize(version));
+
+        data
+    }
+}
@@ -0,0 +1,48 @@
+use crate::byte_utils;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+
+/// A Ping or its Pong. The Pong carries back the nonce of the Ping it answers, so the sender
+/// can match them and measure the round-trip time.
+pub struct KeepaliveInfo {
+    pub message_type: MessageType,
+    pub nonce: u32,
+}
+
+impl KeepaliveInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<KeepaliveInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (_version, message_type) = MessageType::parse_header(message)?;
+
+        byte_utils::require(message, 6)?;
+        let nonce = byte_utils::u32_from_u8_array(&message[2..6]);
+
+        Ok(KeepaliveInfo {
+            message_type,
+            nonce,
+        })
+    }
+
+    pub fn ping(nonce: u32) -> KeepaliveInfo {
+        KeepaliveInfo {
+            message_type: MessageType::Ping,
+            nonce,
+        }
+    }
+
+    pub fn pong(nonce: u32) -> KeepaliveInfo {
+        KeepaliveInfo {
+            message_type: MessageType::Pong,
+            nonce,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(ProtocolVersion::V1).iter());
+        data.extend(self.nonce.to_be_bytes().iter());
+
+        data
+    }
+}
@@ -1,14 +1,60 @@
+/// Identifies a chunk within a content. Version 1 messages carry it as a u16 and version 2
+/// messages as a u32.
+pub type ChunkId = u32;
+
 mod byte_utils;
-pub use byte_utils::u16_from_u8_array;
+pub use byte_utils::{u16_from_u8_array, u32_from_u8_array, u64_from_u8_array};
+
+mod address_utils;
+pub use address_utils::{canonical_address, reachable_address};
+
+mod protocol_error;
+pub use protocol_error::ProtocolError;
+
+mod content_id;
+pub use content_id::{ContentId, MAX_CONTENT_ID_LENGTH};
+
+mod protocol_version;
+pub use protocol_version::ProtocolVersion;
+
+mod message_type;
+pub use message_type::MessageType;
 
 mod chunk_list;
-pub use chunk_list::{ChunkList, ChunkListMessage};
+pub use chunk_list::{ChunkList, ChunkListEncoding, ChunkListMessage, MAX_CHUNK_LIST_LENGTH};
+
+mod hello_info;
+pub use hello_info::HelloInfo;
 
 mod query_info;
 pub use query_info::QueryInfo;
 
 mod response_info;
-pub use response_info::ResponseInfo;
+pub use response_info::{ResponseInfo, MAX_FRAGMENT_SIZE};
+
+mod fragment_request_info;
+pub use fragment_request_info::FragmentRequestInfo;
+
+mod peer_exchange_info;
+pub use peer_exchange_info::PeerExchangeInfo;
+
+mod keepalive_info;
+pub use keepalive_info::KeepaliveInfo;
+
+mod provider_info;
+pub use provider_info::ProviderInfo;
+
+mod dht;
+pub use dht::{chunk_key, distance, Contact, NodeId};
+
+mod lookup_info;
+pub use lookup_info::LookupInfo;
+
+mod nodes_info;
+pub use nodes_info::NodesInfo;
+
+mod add_provider_info;
+pub use add_provider_info::{AddProviderInfo, MAX_KEYS_PER_ANNOUNCEMENT};
 
 mod message;
 pub use message::Message;
@@ -0,0 +1,57 @@
+use crate::byte_utils;
+use crate::dht::{self, NodeId};
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+
+/// A FindNode or FindProviders message. Both ask for the nodes the receiver knows closest to
+/// `target`; FindProviders also asks for the providers recorded for the chunk whose key is
+/// `target`. Either way the answer is a Nodes message.
+pub struct LookupInfo {
+    pub message_type: MessageType,
+    pub sender: Option<NodeId>,
+    pub target: NodeId,
+}
+
+impl LookupInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<LookupInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (_version, message_type) = MessageType::parse_header(message)?;
+        let (sender, sender_length) = dht::parse_sender(&message[2..])?;
+        let target_start = 2 + sender_length;
+
+        byte_utils::require(message, target_start + 8)?;
+        let target = byte_utils::u64_from_u8_array(&message[target_start..target_start + 8]);
+
+        Ok(LookupInfo {
+            message_type,
+            sender,
+            target,
+        })
+    }
+
+    pub fn find_node(sender: Option<NodeId>, target: NodeId) -> LookupInfo {
+        LookupInfo {
+            message_type: MessageType::FindNode,
+            sender,
+            target,
+        }
+    }
+
+    pub fn find_providers(sender: Option<NodeId>, key: NodeId) -> LookupInfo {
+        LookupInfo {
+            message_type: MessageType::FindProviders,
+            sender,
+            target: key,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(ProtocolVersion::V1).iter());
+        data.append(&mut dht::serialize_sender(self.sender));
+        data.extend(self.target.to_be_bytes().iter());
+
+        data
+    }
+}
@@ -1,39 +1,110 @@
+use crate::add_provider_info::AddProviderInfo;
+use crate::byte_utils;
 use crate::chunk_list::ChunkListMessage;
+use crate::fragment_request_info::FragmentRequestInfo;
+use crate::hello_info::HelloInfo;
+use crate::keepalive_info::KeepaliveInfo;
+use crate::lookup_info::LookupInfo;
+use crate::message_type::MessageType;
+use crate::nodes_info::NodesInfo;
+use crate::peer_exchange_info::PeerExchangeInfo;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use crate::provider_info::ProviderInfo;
 use crate::query_info::QueryInfo;
 use crate::response_info::ResponseInfo;
 
 pub enum Message {
-    Hello(ChunkListMessage),
+    Hello(HelloInfo),
     Get(ChunkListMessage),
     Query(QueryInfo),
     ChunkInfo(ChunkListMessage),
     Response(ResponseInfo),
+    GetFragments(FragmentRequestInfo),
+    Join,
+    Bye,
+    PeerExchange(PeerExchangeInfo),
+    Ping(KeepaliveInfo),
+    Pong(KeepaliveInfo),
+    Announce(ChunkListMessage),
+    TrackerQuery(ChunkListMessage),
+    TrackerReply(ProviderInfo),
+    FindNode(LookupInfo),
+    FindProviders(LookupInfo),
+    Nodes(NodesInfo),
+    AddProvider(AddProviderInfo),
+    AnnounceMore(ChunkListMessage),
 }
 
 impl Message {
-    pub fn new(message: &[u8], bytes_read: usize) -> Result<Message, &'static str> {
-        if bytes_read < 2 {
-            return Err("Less than 2 bytes read");
-        }
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<Message, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
 
-        let message_type = message[1];
+        let (_version, message_type) = MessageType::parse_header(message)?;
         match message_type {
-            1 => Ok(Self::Hello(ChunkListMessage::new(message, bytes_read)?)),
-            2 => Ok(Self::Query(QueryInfo::new(message, bytes_read)?)),
-            3 => Ok(Self::ChunkInfo(ChunkListMessage::new(message, bytes_read)?)),
-            4 => Ok(Self::Get(ChunkListMessage::new(message, bytes_read)?)),
-            5 => Ok(Self::Response(ResponseInfo::new(message, bytes_read)?)),
-            _ => Err("Unknown message type"),
+            MessageType::Hello => Ok(Self::Hello(HelloInfo::new(message, bytes_read)?)),
+            MessageType::Query => Ok(Self::Query(QueryInfo::new(message, bytes_read)?)),
+            MessageType::ChunkInfo => {
+                Ok(Self::ChunkInfo(ChunkListMessage::new(message, bytes_read)?))
+            }
+            MessageType::Get => Ok(Self::Get(ChunkListMessage::new(message, bytes_read)?)),
+            MessageType::Response => Ok(Self::Response(ResponseInfo::new(message, bytes_read)?)),
+            MessageType::GetFragments => Ok(Self::GetFragments(FragmentRequestInfo::new(
+                message, bytes_read,
+            )?)),
+            MessageType::Join => Ok(Self::Join),
+            MessageType::Bye => Ok(Self::Bye),
+            MessageType::PeerExchange => Ok(Self::PeerExchange(PeerExchangeInfo::new(
+                message, bytes_read,
+            )?)),
+            MessageType::Ping => Ok(Self::Ping(KeepaliveInfo::new(message, bytes_read)?)),
+            MessageType::Pong => Ok(Self::Pong(KeepaliveInfo::new(message, bytes_read)?)),
+            MessageType::Announce => {
+                Ok(Self::Announce(ChunkListMessage::new(message, bytes_read)?))
+            }
+            MessageType::TrackerQuery => Ok(Self::TrackerQuery(ChunkListMessage::new(
+                message, bytes_read,
+            )?)),
+            MessageType::TrackerReply => {
+                Ok(Self::TrackerReply(ProviderInfo::new(message, bytes_read)?))
+            }
+            MessageType::FindNode => Ok(Self::FindNode(LookupInfo::new(message, bytes_read)?)),
+            MessageType::FindProviders => {
+                Ok(Self::FindProviders(LookupInfo::new(message, bytes_read)?))
+            }
+            MessageType::Nodes => Ok(Self::Nodes(NodesInfo::new(message, bytes_read)?)),
+            MessageType::AddProvider => Ok(Self::AddProvider(AddProviderInfo::new(
+                message, bytes_read,
+            )?)),
+            MessageType::AnnounceMore => Ok(Self::AnnounceMore(ChunkListMessage::new(
+                message, bytes_read,
+            )?)),
         }
     }
 
     pub fn serialize(&self) -> Vec<u8> {
         match self {
-            Message::Hello(list) | Message::ChunkInfo(list) | Message::Get(list) => {
-                list.serialize()
+            Message::Hello(hello_info) => hello_info.serialize(),
+            Message::ChunkInfo(list)
+            | Message::Get(list)
+            | Message::Announce(list)
+            | Message::AnnounceMore(list)
+            | Message::TrackerQuery(list) => list.serialize(),
+            Message::Query(query_info) => query_info.serialize(),
+            Message::Response(response_info) => response_info.serialize(),
+            Message::GetFragments(request_info) => request_info.serialize(),
+            Message::Join => MessageType::Join.header(ProtocolVersion::V1).to_vec(),
+            Message::Bye => MessageType::Bye.header(ProtocolVersion::V1).to_vec(),
+            Message::PeerExchange(exchange_info) => exchange_info.serialize(),
+            Message::Ping(keepalive_info) | Message::Pong(keepalive_info) => {
+                keepalive_info.serialize()
+            }
+            Message::TrackerReply(provider_info) => provider_info.serialize(),
+            Message::FindNode(lookup_info) | Message::FindProviders(lookup_info) => {
+                lookup_info.serialize()
             }
-            Message::Query(_query_info) => todo!("Implementar serialização"),
-            Message::Response(_response_info) => todo!("Implementar serialização"),
+            Message::Nodes(nodes_info) => nodes_info.serialize(),
+            Message::AddProvider(add_provider_info) => add_provider_info.serialize(),
         }
     }
 }
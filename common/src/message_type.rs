@@ -0,0 +1,80 @@
+use crate::byte_utils;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Hello = 1,
+    Query = 2,
+    ChunkInfo = 3,
+    Get = 4,
+    Response = 5,
+    GetFragments = 6,
+    /// Asks the receiver to take the sender as a neighbour.
+    Join = 7,
+    /// Tells the receiver that the sender is leaving the swarm.
+    Bye = 8,
+    PeerExchange = 9,
+    Ping = 10,
+    Pong = 11,
+    /// Tells a tracker which chunks of a content the sender holds, replacing its previous
+    /// announcement for that content.
+    Announce = 12,
+    /// Asks a tracker which peers hold some chunks.
+    TrackerQuery = 13,
+    TrackerReply = 14,
+    FindNode = 15,
+    FindProviders = 16,
+    /// Answer to FindNode and FindProviders.
+    Nodes = 17,
+    AddProvider = 18,
+    /// Adds chunks to the sender's latest Announce for a content, for catalogues too large to
+    /// fit a single datagram.
+    AnnounceMore = 19,
+}
+
+impl MessageType {
+    /// The two bytes starting every message: the protocol version and the message type.
+    pub fn header(self, version: ProtocolVersion) -> [u8; 2] {
+        [version.to_byte(), self as u8]
+    }
+
+    /// Reads the header written by `header`.
+    pub fn parse_header(message: &[u8]) -> Result<(ProtocolVersion, MessageType), ProtocolError> {
+        byte_utils::require(message, 2)?;
+
+        let version = ProtocolVersion::try_from(message[0])?;
+        let message_type = MessageType::try_from(message[1] as u16)?;
+        Ok((version, message_type))
+    }
+}
+
+impl TryFrom<u16> for MessageType {
+    type Error = ProtocolError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(MessageType::Hello),
+            2 => Ok(MessageType::Query),
+            3 => Ok(MessageType::ChunkInfo),
+            4 => Ok(MessageType::Get),
+            5 => Ok(MessageType::Response),
+            6 => Ok(MessageType::GetFragments),
+            7 => Ok(MessageType::Join),
+            8 => Ok(MessageType::Bye),
+            9 => Ok(MessageType::PeerExchange),
+            10 => Ok(MessageType::Ping),
+            11 => Ok(MessageType::Pong),
+            12 => Ok(MessageType::Announce),
+            13 => Ok(MessageType::TrackerQuery),
+            14 => Ok(MessageType::TrackerReply),
+            15 => Ok(MessageType::FindNode),
+            16 => Ok(MessageType::FindProviders),
+            17 => Ok(MessageType::Nodes),
+            18 => Ok(MessageType::AddProvider),
+            19 => Ok(MessageType::AnnounceMore),
+            _ => Err(ProtocolError::UnknownType(value)),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::address_utils;
+use crate::byte_utils;
+use crate::dht::{self, Contact, NodeId};
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use std::net::SocketAddr;
+
+/// Answers a FindNode or FindProviders for `target` with the closest nodes the sender knows
+/// and, for FindProviders, the providers it holds records for.
+pub struct NodesInfo {
+    pub message_type: MessageType,
+    pub sender: Option<NodeId>,
+    pub target: NodeId,
+    pub providers: Vec<SocketAddr>,
+    pub nodes: Vec<Contact>,
+}
+
+impl NodesInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<NodesInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (_version, message_type) = MessageType::parse_header(message)?;
+        let (sender, sender_length) = dht::parse_sender(&message[2..])?;
+        let mut start = 2 + sender_length;
+
+        byte_utils::require(message, start + 8)?;
+        let target = byte_utils::u64_from_u8_array(&message[start..start + 8]);
+        start += 8;
+
+        byte_utils::require(message, start + 2)?;
+        let amount_of_providers = byte_utils::u16_from_u8_array(&message[start..start + 2]);
+        start += 2;
+        let mut providers = Vec::new();
+        for _ in 0..amount_of_providers {
+            let (address, address_length) = address_utils::parse_address(&message[start..])?;
+            providers.push(address);
+            start += address_length;
+        }
+
+        byte_utils::require(message, start + 2)?;
+        let amount_of_nodes = byte_utils::u16_from_u8_array(&message[start..start + 2]);
+        start += 2;
+        let mut nodes = Vec::new();
+        for _ in 0..amount_of_nodes {
+            let (contact, contact_length) = Contact::parse(&message[start..])?;
+            nodes.push(contact);
+            start += contact_length;
+        }
+
+        if start != message.len() {
+            return Err(ProtocolError::LengthMismatch {
+                declared: start,
+                actual: message.len(),
+            });
+        }
+
+        Ok(NodesInfo {
+            message_type,
+            sender,
+            target,
+            providers,
+            nodes,
+        })
+    }
+
+    pub fn from_nodes(
+        sender: Option<NodeId>,
+        target: NodeId,
+        providers: Vec<SocketAddr>,
+        nodes: Vec<Contact>,
+    ) -> NodesInfo {
+        NodesInfo {
+            message_type: MessageType::Nodes,
+            sender,
+            target,
+            providers,
+            nodes,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(ProtocolVersion::V1).iter());
+        data.append(&mut dht::serialize_sender(self.sender));
+        data.extend(self.target.to_be_bytes().iter());
+
+        data.extend((self.providers.len() as u16).to_be_bytes().iter());
+        for provider in &self.providers {
+            data.append(&mut address_utils::serialize_address(provider));
+        }
+
+        data.extend((self.nodes.len() as u16).to_be_bytes().iter());
+        for node in &self.nodes {
+            data.append(&mut node.serialize());
+        }
+
+        data
+    }
+}
@@ -0,0 +1,65 @@
+use crate::address_utils;
+use crate::byte_utils;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use std::net::SocketAddr;
+
+/// A sample of the sender's neighbours, sent in answer to a Join and from time to time to
+/// a random neighbour, so that nodes learn about the rest of the swarm.
+pub struct PeerExchangeInfo {
+    pub message_type: MessageType,
+    pub peers: Vec<SocketAddr>,
+}
+
+impl PeerExchangeInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<PeerExchangeInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (_version, message_type) = MessageType::parse_header(message)?;
+
+        byte_utils::require(message, 4)?;
+        let amount_of_peers = byte_utils::u16_from_u8_array(&message[2..4]) as usize;
+
+        // Every address takes at least 7 bytes, so the count is bounded by the datagram.
+        let mut peers = Vec::new();
+        let mut start = 4;
+        for _ in 0..amount_of_peers {
+            let (address, address_length) = address_utils::parse_address(&message[start..])?;
+            peers.push(address);
+            start += address_length;
+        }
+
+        if start != message.len() {
+            return Err(ProtocolError::LengthMismatch {
+                declared: start,
+                actual: message.len(),
+            });
+        }
+
+        Ok(PeerExchangeInfo {
+            message_type,
+            peers,
+        })
+    }
+
+    /// Builds a message carrying `peers`. Only the first `u16::MAX` addresses are kept.
+    pub fn from_peers(mut peers: Vec<SocketAddr>) -> PeerExchangeInfo {
+        peers.truncate(u16::MAX as usize);
+
+        PeerExchangeInfo {
+            message_type: MessageType::PeerExchange,
+            peers,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(ProtocolVersion::V1).iter());
+        data.extend((self.peers.len() as u16).to_be_bytes().iter());
+        for peer in &self.peers {
+            data.append(&mut address_utils::serialize_address(peer));
+        }
+
+        data
+    }
+}
@@ -0,0 +1,60 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    Truncated { expected: usize, got: usize },
+    UnknownType(u16),
+    UnknownVersion(u8),
+    LengthMismatch { declared: usize, actual: usize },
+    BadAddress,
+    InvalidFragment { index: u16, count: u16 },
+    InvalidContentId,
+    UnknownEncoding(u8),
+    InvalidRange { start: u32, end: u32 },
+    TooManyChunks { max: usize },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Truncated { expected, got } => write!(
+                f,
+                "Message truncated: expected at least {} bytes, got {}",
+                expected, got
+            ),
+            ProtocolError::UnknownType(message_type) => {
+                write!(f, "Unknown message type {}", message_type)
+            }
+            ProtocolError::UnknownVersion(version) => {
+                write!(f, "Unknown protocol version {}", version)
+            }
+            ProtocolError::LengthMismatch { declared, actual } => write!(
+                f,
+                "Length mismatch: message declares {} bytes, carries {}",
+                declared, actual
+            ),
+            ProtocolError::BadAddress => write!(f, "Message contains an invalid address"),
+            ProtocolError::InvalidFragment { index, count } => write!(
+                f,
+                "Invalid fragment: index {} of a chunk with {} fragments",
+                index, count
+            ),
+            ProtocolError::InvalidContentId => write!(
+                f,
+                "Content ID is not valid UTF-8, longer than {} bytes or has an empty, '.' or '..' segment",
+                crate::MAX_CONTENT_ID_LENGTH
+            ),
+            ProtocolError::UnknownEncoding(encoding) => {
+                write!(f, "Unknown chunk list encoding {}", encoding)
+            }
+            ProtocolError::InvalidRange { start, end } => {
+                write!(f, "Invalid chunk range {}-{}", start, end)
+            }
+            ProtocolError::TooManyChunks { max } => {
+                write!(f, "Chunk list expands to more than {} chunks", max)
+            }
+        }
+    }
+}
+
+impl Error for ProtocolError {}
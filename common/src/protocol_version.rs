@@ -0,0 +1,71 @@
+use crate::protocol_error::ProtocolError;
+use std::convert::TryFrom;
+
+/// Version of the wire format, carried in the high byte of the message type field.
+///
+/// Version 1 keeps the u16 chunk IDs and the zero high byte of the original format, but is
+/// not understood by peers running the original code: every message now carries a content ID
+/// after its type, and Query also carries a query ID and an address family. Both ends
+/// must run this protocol. Version 2 widens chunk IDs and chunk-list counts to u32, and is
+/// only used by messages that need it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    V1,
+    V2,
+}
+
+impl ProtocolVersion {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            ProtocolVersion::V1 => 0,
+            ProtocolVersion::V2 => 2,
+        }
+    }
+
+    /// The oldest version able to carry `value` as a chunk ID or chunk-list count.
+    pub fn for_value(value: usize) -> ProtocolVersion {
+        if value > u16::MAX as usize {
+            ProtocolVersion::V2
+        } else {
+            ProtocolVersion::V1
+        }
+    }
+
+    /// Size in bytes of chunk IDs and chunk-list counts in this version.
+    pub(crate) fn id_size(self) -> usize {
+        match self {
+            ProtocolVersion::V1 => 2,
+            ProtocolVersion::V2 => 4,
+        }
+    }
+
+    /// Writes a chunk ID or chunk-list count with the width of this version. Callers pick a
+    /// version with `for_value`, so the value always fits.
+    pub(crate) fn serialize_id(self, value: u32) -> Vec<u8> {
+        match self {
+            ProtocolVersion::V1 => Vec::from((value as u16).to_be_bytes()),
+            ProtocolVersion::V2 => Vec::from(value.to_be_bytes()),
+        }
+    }
+
+    /// Reads a chunk ID or chunk-list count written by `serialize_id`. `message` must hold at
+    /// least `id_size()` bytes.
+    pub(crate) fn parse_id(self, message: &[u8]) -> u32 {
+        match self {
+            ProtocolVersion::V1 => crate::u16_from_u8_array(message) as u32,
+            ProtocolVersion::V2 => crate::u32_from_u8_array(message),
+        }
+    }
+}
+
+impl TryFrom<u8> for ProtocolVersion {
+    type Error = ProtocolError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ProtocolVersion::V1),
+            2 => Ok(ProtocolVersion::V2),
+            _ => Err(ProtocolError::UnknownVersion(value)),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::address_utils;
+use crate::byte_utils;
+use crate::chunk_list::ChunkList;
+use crate::content_id::ContentId;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::ChunkId;
+use std::net::SocketAddr;
+
+/// Sent by the tracker in answer to a TrackerQuery, once for every peer announcing some of the
+/// chunks asked for: `address` holds the listed chunks of `content`.
+pub struct ProviderInfo {
+    pub message_type: MessageType,
+    pub content: ContentId,
+    pub address: SocketAddr,
+    pub chunk_list: ChunkList,
+}
+
+impl ProviderInfo {
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<ProviderInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (version, message_type) = MessageType::parse_header(message)?;
+        let (content, content_length) = ContentId::parse(&message[2..])?;
+        let address_start = 2 + content_length;
+        let (address, address_length) = address_utils::parse_address(&message[address_start..])?;
+        let list_start = address_start + address_length;
+        let chunk_list =
+            ChunkList::new(&message[list_start..], message.len() - list_start, version)?;
+
+        Ok(ProviderInfo {
+            message_type,
+            content,
+            address,
+            chunk_list,
+        })
+    }
+
+    pub fn from_chunks(
+        content: ContentId,
+        address: SocketAddr,
+        chunks: Vec<ChunkId>,
+    ) -> ProviderInfo {
+        ProviderInfo {
+            message_type: MessageType::TrackerReply,
+            content,
+            address,
+            chunk_list: ChunkList::from_chunks(chunks),
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let version = self.chunk_list.version();
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(version).iter());
+        data.append(&mut self.content.serialize());
+        data.append(&mut address_utils::serialize_address(&self.address));
+        data.append(&mut self.chunk_list.serialize(version));
+
+        data
+    }
+}
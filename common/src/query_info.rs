@@ -1,53 +1,66 @@
+use crate::address_utils;
 use crate::byte_utils;
 use crate::chunk_list::ChunkList;
-use core::panic;
-use std::net::{IpAddr, SocketAddr};
+use crate::content_id::ContentId;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use std::net::SocketAddr;
 
 pub struct QueryInfo {
-    pub message_type: u16,
+    pub message_type: MessageType,
+    pub content: ContentId,
+    /// Chosen by the peer that started the query. Together with `address` it identifies the
+    /// query, so peers can drop copies reaching them through different paths.
+    pub query_id: u32,
     pub address: SocketAddr,
     pub peer_ttl: u16,
     pub chunk_info: ChunkList,
 }
 
 impl QueryInfo {
-    pub fn new(message: &[u8], bytes_read: usize) -> Result<QueryInfo, &'static str> {
-        if bytes_read < 12 {
-            return Err(
-                "Less than 12 bytes read for message that should contain at least 12 bytes.",
-            );
-        }
-
-        let message_type = byte_utils::u16_from_u8_array(&message[0..2]);
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<QueryInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (version, message_type) = MessageType::parse_header(message)?;
 
-        println!("[DEBUG] MessageType={}", message_type);
+        let (content, content_length) = ContentId::parse(&message[2..])?;
+        let id_start = 2 + content_length;
 
-        let address = &message[2..8];
-        let ip_octets = &address[0..4];
-        let port = byte_utils::u16_from_u8_array(&address[4..6]);
-        let address: SocketAddr = format!(
-            "{}.{}.{}.{}:{}",
-            ip_octets[0], ip_octets[1], ip_octets[2], ip_octets[3], port
-        )
-        .parse()
-        .expect("Failed to parse address");
+        byte_utils::require(message, id_start + 4)?;
+        let query_id = byte_utils::u32_from_u8_array(&message[id_start..id_start + 4]);
+        let (address, address_length) = address_utils::parse_address(&message[id_start + 4..])?;
+        let ttl_start = id_start + 4 + address_length;
 
-        let peer_ttl = byte_utils::u16_from_u8_array(&message[8..10]);
-        let chunk_info = ChunkList::new(&message[10..], bytes_read)?;
+        byte_utils::require(message, ttl_start + 2)?;
+        let peer_ttl = byte_utils::u16_from_u8_array(&message[ttl_start..ttl_start + 2]);
+        let chunk_info = ChunkList::new(
+            &message[ttl_start + 2..],
+            message.len() - ttl_start - 2,
+            version,
+        )?;
 
         Ok(QueryInfo {
             message_type,
+            content,
+            query_id,
             address,
             peer_ttl,
             chunk_info,
         })
     }
 
-    pub fn from_chunks(address: SocketAddr, chunk_info: ChunkList) -> QueryInfo {
+    pub fn from_chunks(
+        content: ContentId,
+        query_id: u32,
+        address: SocketAddr,
+        peer_ttl: u16,
+        chunk_info: ChunkList,
+    ) -> QueryInfo {
         QueryInfo {
-            message_type: 2,
+            message_type: MessageType::Query,
+            content,
+            query_id,
             address,
-            peer_ttl: 3,
+            peer_ttl,
             chunk_info,
         }
     }
@@ -55,25 +68,24 @@ impl QueryInfo {
     pub fn with_decremented_ttl(&self) -> QueryInfo {
         QueryInfo {
             message_type: self.message_type,
+            content: self.content.clone(),
+            query_id: self.query_id,
             address: self.address,
             chunk_info: self.chunk_info.clone(),
-            peer_ttl: self.peer_ttl - 1,
+            peer_ttl: self.peer_ttl.saturating_sub(1),
         }
     }
 
     pub fn serialize(&self) -> Vec<u8> {
-        let mut data: Vec<u8> = Vec::new();
-        data.extend(self.message_type.to_be_bytes().iter());
-
-        let ip_octets = match self.address.ip() {
-            IpAddr::V4(ip) => ip.octets(),
-            _ => panic!("IPv6 not supported"),
-        };
+        let version = self.chunk_info.version();
 
-        data.append(&mut ip_octets.to_vec());
-        data.extend(self.address.port().to_be_bytes().iter());
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(self.message_type.header(version).iter());
+        data.append(&mut self.content.serialize());
+        data.extend(self.query_id.to_be_bytes().iter());
+        data.append(&mut address_utils::serialize_address(&self.address));
         data.extend(self.peer_ttl.to_be_bytes().iter());
-        data.append(&mut self.chunk_info.serialize());
+        data.append(&mut self.chunk_info.serialize(version));
 
         data
     }
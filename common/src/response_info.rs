@@ -1,50 +1,125 @@
 use crate::byte_utils;
+use crate::content_id::ContentId;
+use crate::message_type::MessageType;
+use crate::protocol_error::ProtocolError;
+use crate::protocol_version::ProtocolVersion;
+use crate::ChunkId;
+use std::convert::TryFrom;
+
+/// Largest amount of chunk bytes carried by a single Response datagram.
+pub const MAX_FRAGMENT_SIZE: usize = 8 * 1024;
 
 pub struct ResponseInfo {
-    pub message_type: u16,
-    pub chunk_id: u16,
-    pub chunk_size: u16,
-    pub chunk: Vec<u8>,
+    pub message_type: MessageType,
+    pub content: ContentId,
+    pub chunk_id: ChunkId,
+    pub fragment_index: u16,
+    pub fragment_count: u16,
+    pub fragment_size: u16,
+    pub fragment: Vec<u8>,
 }
 
 impl ResponseInfo {
-    pub fn new(message: &[u8], bytes_read: usize) -> Result<ResponseInfo, &'static str> {
-        if bytes_read < 6 {
-            return Err(
-                "Less than 6 bytes read for message that should contain at least 6 bytes.",
-            );
-        }
+    pub fn new(message: &[u8], bytes_read: usize) -> Result<ResponseInfo, ProtocolError> {
+        let message = byte_utils::received(message, bytes_read)?;
+        let (version, message_type) = MessageType::parse_header(message)?;
+        let (content, content_length) = ContentId::parse(&message[2..])?;
+        let header = &message[2 + content_length..];
 
-        let message_type = byte_utils::u16_from_u8_array(&message[0..2]);
-        let chunk_id = byte_utils::u16_from_u8_array(&message[2..4]);
-        let chunk_size = byte_utils::u16_from_u8_array(&message[4..6]);
-        let chunk = Vec::from(&message[6..bytes_read]);
+        let id_size = version.id_size();
+        byte_utils::require(header, id_size + 6)?;
+        let chunk_id = version.parse_id(&header[0..id_size]);
+        let header = &header[id_size..];
+        let fragment_index = byte_utils::u16_from_u8_array(&header[0..2]);
+        let fragment_count = byte_utils::u16_from_u8_array(&header[2..4]);
+        let fragment_size = byte_utils::u16_from_u8_array(&header[4..6]);
+        let fragment = Vec::from(&header[6..]);
 
-        println!("Chunk size is: {}. {} bytes read.", chunk.len(), bytes_read);
+        if fragment_index >= fragment_count {
+            return Err(ProtocolError::InvalidFragment {
+                index: fragment_index,
+                count: fragment_count,
+            });
+        }
+
+        if fragment.len() != fragment_size as usize {
+            return Err(ProtocolError::LengthMismatch {
+                declared: fragment_size as usize,
+                actual: fragment.len(),
+            });
+        }
 
         Ok(ResponseInfo {
             message_type,
+            content,
             chunk_id,
-            chunk_size,
-            chunk,
+            fragment_index,
+            fragment_count,
+            fragment_size,
+            fragment,
         })
     }
 
-    pub fn from_chunk(chunk_id: u16, chunk: Vec<u8>) -> ResponseInfo {
-        ResponseInfo {
-            message_type: 5,
-            chunk_id,
-            chunk_size: chunk.len() as u16,
-            chunk,
+    /// Amount of fragments needed to carry a chunk of `chunk_size` bytes, or `None` if the
+    /// chunk is too large to be described by a u16 fragment count.
+    pub fn fragment_count(chunk_size: usize) -> Option<u16> {
+        let count = chunk_size.div_ceil(MAX_FRAGMENT_SIZE);
+        u16::try_from(count.max(1)).ok()
+    }
+
+    /// Builds the Response carrying fragment `fragment_index` of `chunk`, or `None` if the
+    /// chunk has no such fragment.
+    pub fn from_chunk_fragment(
+        content: &ContentId,
+        chunk_id: ChunkId,
+        chunk: &[u8],
+        fragment_index: u16,
+    ) -> Option<ResponseInfo> {
+        let fragment_count = ResponseInfo::fragment_count(chunk.len())?;
+        if fragment_index >= fragment_count {
+            return None;
         }
+
+        let start = fragment_index as usize * MAX_FRAGMENT_SIZE;
+        let end = (start + MAX_FRAGMENT_SIZE).min(chunk.len());
+        let fragment = Vec::from(&chunk[start..end]);
+
+        Some(ResponseInfo {
+            message_type: MessageType::Response,
+            content: content.clone(),
+            chunk_id,
+            fragment_index,
+            fragment_count,
+            fragment_size: fragment.len() as u16,
+            fragment,
+        })
+    }
+
+    /// Splits `chunk` into all the Responses needed to transfer it. Fragments are copied out
+    /// of `chunk` one at a time, as the iterator advances.
+    pub fn from_chunk<'a>(
+        content: &'a ContentId,
+        chunk_id: ChunkId,
+        chunk: &'a [u8],
+    ) -> impl Iterator<Item = ResponseInfo> + 'a {
+        let fragment_count = ResponseInfo::fragment_count(chunk.len()).unwrap_or(0);
+
+        (0..fragment_count).filter_map(move |index| {
+            ResponseInfo::from_chunk_fragment(content, chunk_id, chunk, index)
+        })
     }
 
-    pub fn serialize(&mut self) -> Vec<u8> {
+    pub fn serialize(&self) -> Vec<u8> {
+        let version = ProtocolVersion::for_value(self.chunk_id as usize);
+
         let mut data: Vec<u8> = Vec::new();
-        data.extend(self.message_type.to_be_bytes().iter());
-        data.extend(self.chunk_id.to_be_bytes().iter());
-        data.extend(self.chunk_size.to_be_bytes().iter());
-        data.append(&mut self.chunk);
+        data.extend(self.message_type.header(version).iter());
+        data.append(&mut self.content.serialize());
+        data.append(&mut version.serialize_id(self.chunk_id));
+        data.extend(self.fragment_index.to_be_bytes().iter());
+        data.extend(self.fragment_count.to_be_bytes().iter());
+        data.extend(self.fragment_size.to_be_bytes().iter());
+        data.extend(self.fragment.iter());
 
         data
     }
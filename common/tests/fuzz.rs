@@ -0,0 +1,48 @@
+use common::{ChunkList, Message, ProtocolError, ProtocolVersion};
+use proptest::{collection::vec, prelude::*};
+
+proptest! {
+    #[test]
+    fn message_new_never_panics(bytes in vec(any::<u8>(), 0..512), bytes_read in 0usize..600) {
+        let _ = Message::new(&bytes, bytes_read);
+        let _ = Message::new(&bytes, bytes.len());
+    }
+
+    #[test]
+    fn message_new_never_panics_on_known_types(
+        version in prop_oneof![Just(0u8), Just(2u8)],
+        message_type in 0u8..20,
+        body in vec(any::<u8>(), 0..512),
+    ) {
+        let mut bytes = vec![version, message_type];
+        bytes.extend(body);
+
+        let _ = Message::new(&bytes, bytes.len());
+    }
+
+    #[test]
+    fn chunk_list_new_never_panics(bytes in vec(any::<u8>(), 0..512), bytes_read in 0usize..600) {
+        let _ = ChunkList::new(&bytes, bytes_read, ProtocolVersion::V1);
+        let _ = ChunkList::new(&bytes, bytes_read, ProtocolVersion::V2);
+    }
+}
+
+#[test]
+fn chunk_list_with_inflated_count_is_rejected() {
+    let bytes = [0xff, 0xff, 0, 1];
+    assert!(ChunkList::new(&bytes, bytes.len(), ProtocolVersion::V1).is_err());
+
+    let bytes = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1];
+    assert!(ChunkList::new(&bytes, bytes.len(), ProtocolVersion::V2).is_err());
+}
+
+#[test]
+fn bytes_read_beyond_buffer_is_rejected() {
+    assert_eq!(
+        Message::new(&[0, 1, 0, 0], 100).err(),
+        Some(ProtocolError::LengthMismatch {
+            declared: 100,
+            actual: 4
+        })
+    );
+}
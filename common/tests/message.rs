@@ -0,0 +1,734 @@
+use common::{
+    AddProviderInfo, ChunkList, ChunkListEncoding, ChunkListMessage, Contact, ContentId,
+    FragmentRequestInfo, HelloInfo, KeepaliveInfo, LookupInfo, Message, MessageType, NodesInfo,
+    PeerExchangeInfo, ProtocolError, ProtocolVersion, ProviderInfo, QueryInfo, ResponseInfo,
+    MAX_CHUNK_LIST_LENGTH, MAX_CONTENT_ID_LENGTH, MAX_FRAGMENT_SIZE,
+};
+use std::net::SocketAddr;
+
+fn round_trip(message: &Message) -> Message {
+    let bytes = message.serialize();
+    Message::new(&bytes, bytes.len()).expect("Failed to parse serialized message")
+}
+
+fn chunk_list(chunks: Vec<u32>) -> ChunkList {
+    ChunkList::from_chunks(chunks)
+}
+
+fn content(name: &str) -> ContentId {
+    ContentId::new(name).unwrap()
+}
+
+#[test]
+fn hello_round_trip() {
+    let message = Message::Hello(HelloInfo::from_chunks(
+        content("sintel"),
+        2,
+        vec![1, 2, 300],
+    ));
+
+    match round_trip(&message) {
+        Message::Hello(data) => {
+            assert_eq!(data.message_type, MessageType::Hello);
+            assert_eq!(data.content, content("sintel"));
+            assert_eq!(data.peer_ttl, 2);
+            assert_eq!(data.chunk_list.chunks, vec![1, 2, 300]);
+        }
+        _ => panic!("Expected Hello"),
+    }
+}
+
+#[test]
+fn chunk_info_round_trip() {
+    let message = Message::ChunkInfo(ChunkListMessage::from_chunks(
+        MessageType::ChunkInfo,
+        content("big-buck-bunny"),
+        vec![5, 6],
+    ));
+
+    match round_trip(&message) {
+        Message::ChunkInfo(data) => {
+            assert_eq!(data.message_type, MessageType::ChunkInfo);
+            assert_eq!(data.content, content("big-buck-bunny"));
+            assert_eq!(data.chunk_list.chunks, vec![5, 6]);
+        }
+        _ => panic!("Expected ChunkInfo"),
+    }
+}
+
+#[test]
+fn get_round_trip() {
+    let message = Message::Get(ChunkListMessage::from_chunks(
+        MessageType::Get,
+        ContentId::default(),
+        vec![],
+    ));
+
+    match round_trip(&message) {
+        Message::Get(data) => {
+            assert_eq!(data.message_type, MessageType::Get);
+            assert!(data.content.is_default());
+            assert!(data.chunk_list.chunks.is_empty());
+        }
+        _ => panic!("Expected Get"),
+    }
+}
+
+#[test]
+fn query_round_trip() {
+    let address: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let message = Message::Query(QueryInfo::from_chunks(
+        content("sintel"),
+        0xdead_beef,
+        address,
+        3,
+        chunk_list(vec![7, 8, 9]),
+    ));
+
+    match round_trip(&message) {
+        Message::Query(data) => {
+            assert_eq!(data.message_type, MessageType::Query);
+            assert_eq!(data.content, content("sintel"));
+            assert_eq!(data.query_id, 0xdead_beef);
+            assert_eq!(data.address, address);
+            assert_eq!(data.peer_ttl, 3);
+            assert_eq!(data.chunk_info.chunks, vec![7, 8, 9]);
+        }
+        _ => panic!("Expected Query"),
+    }
+}
+
+#[test]
+fn response_round_trip() {
+    let chunk = vec![0xde, 0xad, 0xbe, 0xef];
+    let video = content("sintel");
+    let mut fragments: Vec<ResponseInfo> = ResponseInfo::from_chunk(&video, 10, &chunk).collect();
+    assert_eq!(fragments.len(), 1);
+    let message = Message::Response(fragments.remove(0));
+
+    match round_trip(&message) {
+        Message::Response(data) => {
+            assert_eq!(data.message_type, MessageType::Response);
+            assert_eq!(data.content, video);
+            assert_eq!(data.chunk_id, 10);
+            assert_eq!(data.fragment_index, 0);
+            assert_eq!(data.fragment_count, 1);
+            assert_eq!(data.fragment_size, 4);
+            assert_eq!(data.fragment, chunk);
+        }
+        _ => panic!("Expected Response"),
+    }
+}
+
+#[test]
+fn response_serialization_does_not_consume_fragment() {
+    let response =
+        ResponseInfo::from_chunk_fragment(&ContentId::default(), 1, &[1, 2, 3], 0).unwrap();
+
+    assert_eq!(response.serialize(), response.serialize());
+    assert_eq!(response.fragment, vec![1, 2, 3]);
+}
+
+#[test]
+fn large_chunk_is_split_into_fragments() {
+    let chunk: Vec<u8> = (0..MAX_FRAGMENT_SIZE * 2 + 10)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let video = ContentId::default();
+    let fragments: Vec<ResponseInfo> = ResponseInfo::from_chunk(&video, 3, &chunk).collect();
+
+    assert_eq!(fragments.len(), 3);
+    assert!(fragments
+        .iter()
+        .all(|fragment| fragment.fragment_count == 3));
+    assert_eq!(fragments[2].fragment.len(), 10);
+
+    let reassembled: Vec<u8> = fragments
+        .iter()
+        .flat_map(|fragment| fragment.fragment.clone())
+        .collect();
+    assert_eq!(reassembled, chunk);
+}
+
+#[test]
+fn get_fragments_round_trip() {
+    let message = Message::GetFragments(FragmentRequestInfo::from_fragments(
+        content("sintel"),
+        4,
+        vec![0, 2],
+    ));
+
+    match round_trip(&message) {
+        Message::GetFragments(data) => {
+            assert_eq!(data.message_type, MessageType::GetFragments);
+            assert_eq!(data.content, content("sintel"));
+            assert_eq!(data.chunk_id, 4);
+            assert_eq!(data.fragments.chunks, vec![0, 2]);
+        }
+        _ => panic!("Expected GetFragments"),
+    }
+}
+
+#[test]
+fn rejects_short_message() {
+    assert_eq!(
+        Message::new(&[0], 1).err(),
+        Some(ProtocolError::Truncated {
+            expected: 2,
+            got: 1
+        })
+    );
+}
+
+#[test]
+fn rejects_unknown_message_type() {
+    assert_eq!(
+        Message::new(&[0, 42], 2).err(),
+        Some(ProtocolError::UnknownType(42))
+    );
+}
+
+#[test]
+fn rejects_response_with_wrong_fragment_size() {
+    let bytes = [0, 5, 0, 0, 1, 0, 0, 0, 1, 0, 10, 1, 2, 3];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::LengthMismatch {
+            declared: 10,
+            actual: 3
+        })
+    );
+}
+
+#[test]
+fn rejects_response_with_fragment_index_out_of_range() {
+    let bytes = [0, 5, 0, 0, 1, 0, 2, 0, 2, 0, 0];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::InvalidFragment { index: 2, count: 2 })
+    );
+}
+
+#[test]
+fn rejects_unknown_protocol_version() {
+    let bytes = [1, 1, 0, 0, 0, 0, 0];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::UnknownVersion(1))
+    );
+}
+
+#[test]
+fn small_chunk_ids_use_version_1() {
+    let bytes = HelloInfo::from_chunks(ContentId::default(), 3, vec![1, 65535]).serialize();
+
+    assert_eq!(bytes, vec![0, 1, 0, 0, 3, 0, 2, 0, 1, 255, 255]);
+}
+
+#[test]
+fn large_chunk_ids_use_version_2() {
+    let bytes = HelloInfo::from_chunks(ContentId::default(), 3, vec![1, 70_000]).serialize();
+    assert_eq!(bytes[0], ProtocolVersion::V2.to_byte());
+
+    match Message::new(&bytes, bytes.len()) {
+        Ok(Message::Hello(data)) => {
+            assert_eq!(data.peer_ttl, 3);
+            assert_eq!(data.chunk_list.chunks, vec![1, 70_000]);
+        }
+        _ => panic!("Expected Hello"),
+    }
+}
+
+#[test]
+fn long_chunk_list_uses_version_2() {
+    let chunks: Vec<u32> = (0..70_000).map(|chunk| chunk % 10).collect();
+    let message = Message::Get(ChunkListMessage::from_chunks(
+        MessageType::Get,
+        ContentId::default(),
+        chunks.clone(),
+    ));
+
+    match round_trip(&message) {
+        Message::Get(data) => {
+            assert_eq!(data.chunk_list.amount_of_chunks, 70_000);
+            assert_eq!(data.chunk_list.chunks, chunks);
+        }
+        _ => panic!("Expected Get"),
+    }
+}
+
+#[test]
+fn version_2_query_round_trip() {
+    let address: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let message = Message::Query(QueryInfo::from_chunks(
+        ContentId::default(),
+        9,
+        address,
+        2,
+        chunk_list(vec![100_000]),
+    ));
+
+    match round_trip(&message) {
+        Message::Query(data) => {
+            assert_eq!(data.address, address);
+            assert_eq!(data.peer_ttl, 2);
+            assert_eq!(data.chunk_info.chunks, vec![100_000]);
+        }
+        _ => panic!("Expected Query"),
+    }
+}
+
+#[test]
+fn version_2_response_and_fragment_request_round_trip() {
+    let video = ContentId::default();
+    let response = ResponseInfo::from_chunk_fragment(&video, 1 << 20, &[7, 8], 0).unwrap();
+
+    match round_trip(&Message::Response(response)) {
+        Message::Response(data) => {
+            assert_eq!(data.chunk_id, 1 << 20);
+            assert_eq!(data.fragment, vec![7, 8]);
+        }
+        _ => panic!("Expected Response"),
+    }
+
+    let request = FragmentRequestInfo::from_fragments(video, 1 << 20, vec![3]);
+    match round_trip(&Message::GetFragments(request)) {
+        Message::GetFragments(data) => {
+            assert_eq!(data.chunk_id, 1 << 20);
+            assert_eq!(data.fragments.chunks, vec![3]);
+        }
+        _ => panic!("Expected GetFragments"),
+    }
+}
+
+#[test]
+fn query_round_trip_ipv6() {
+    let address: SocketAddr = "[2001:db8::1]:5000".parse().unwrap();
+    let message = Message::Query(QueryInfo::from_chunks(
+        ContentId::default(),
+        1,
+        address,
+        3,
+        chunk_list(vec![1]),
+    ));
+
+    match round_trip(&message) {
+        Message::Query(data) => {
+            assert_eq!(data.address, address);
+            assert_eq!(data.chunk_info.chunks, vec![1]);
+        }
+        _ => panic!("Expected Query"),
+    }
+}
+
+#[test]
+fn rejects_query_with_unknown_address_family() {
+    let bytes = [0, 2, 0, 0, 0, 0, 1, 5, 127, 0, 0, 1, 0, 80, 0, 3, 0, 0];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::BadAddress)
+    );
+}
+
+#[test]
+fn query_ttl_decrement_saturates() {
+    let address: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let query = QueryInfo::from_chunks(ContentId::default(), 1, address, 0, chunk_list(vec![1]));
+
+    assert_eq!(query.with_decremented_ttl().peer_ttl, 0);
+}
+
+#[test]
+fn rejects_content_id_with_invalid_utf8() {
+    let bytes = [0, 3, 2, 0xff, 0xfe, 0, 0];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::InvalidContentId)
+    );
+}
+
+#[test]
+fn rejects_truncated_content_id() {
+    let bytes = [0, 4, 5, b'a', b'b'];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::Truncated {
+            expected: 6,
+            got: 3
+        })
+    );
+}
+
+#[test]
+fn content_id_longer_than_limit_is_rejected() {
+    let name = "a".repeat(MAX_CONTENT_ID_LENGTH + 1);
+
+    assert_eq!(
+        ContentId::new(&name).err(),
+        Some(ProtocolError::InvalidContentId)
+    );
+    assert!(ContentId::new(&name[1..]).is_ok());
+}
+
+#[test]
+fn content_id_must_be_a_relative_path() {
+    for name in &["/etc", "../x", "a/../b", "a//b", "a/", "./a", "sintel/."] {
+        assert_eq!(
+            ContentId::new(name).err(),
+            Some(ProtocolError::InvalidContentId),
+            "{}",
+            name
+        );
+    }
+    assert!(ContentId::new("sintel/720p").is_ok());
+    assert!(ContentId::new("a..b").is_ok());
+}
+
+#[test]
+fn rejects_content_id_escaping_the_store() {
+    let mut bytes = vec![0, 3, 4];
+    bytes.extend(b"../x");
+    bytes.extend(&[0, 0]);
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::InvalidContentId)
+    );
+}
+
+#[test]
+fn contiguous_chunks_are_sent_as_ranges() {
+    let chunks: Vec<u32> = (1..=5000).chain(6000..=6001).collect();
+    let list = chunk_list(chunks.clone());
+    assert_eq!(
+        list.encoding(),
+        (ProtocolVersion::V2, ChunkListEncoding::Ranges)
+    );
+
+    let message = Message::Hello(HelloInfo::from_chunks(
+        ContentId::default(),
+        3,
+        chunks.clone(),
+    ));
+    let bytes = message.serialize();
+    assert!(bytes.len() < 32);
+
+    match round_trip(&message) {
+        Message::Hello(data) => assert_eq!(data.chunk_list.chunks, chunks),
+        _ => panic!("Expected Hello"),
+    }
+}
+
+#[test]
+fn scattered_chunks_are_sent_as_a_bitfield() {
+    let chunks: Vec<u32> = (0..2000).step_by(2).collect();
+    let list = chunk_list(chunks.clone());
+    assert_eq!(
+        list.encoding(),
+        (ProtocolVersion::V2, ChunkListEncoding::Bitfield)
+    );
+
+    let message = Message::ChunkInfo(ChunkListMessage::from_chunks(
+        MessageType::ChunkInfo,
+        ContentId::default(),
+        chunks.clone(),
+    ));
+    match round_trip(&message) {
+        Message::ChunkInfo(data) => assert_eq!(data.chunk_list.chunks, chunks),
+        _ => panic!("Expected ChunkInfo"),
+    }
+}
+
+#[test]
+fn bitfields_never_span_more_chunks_than_decoders_accept() {
+    let chunks: Vec<u32> = (0..40_000).map(|chunk| chunk * 30).collect();
+    let list = chunk_list(chunks.clone());
+    assert_eq!(
+        list.encoding(),
+        (ProtocolVersion::V2, ChunkListEncoding::Explicit)
+    );
+
+    let bytes = list.serialize(ProtocolVersion::V2);
+    let decoded = ChunkList::new(&bytes, bytes.len(), ProtocolVersion::V2).unwrap();
+    assert_eq!(decoded.chunks, chunks);
+}
+
+#[test]
+fn longest_accepted_list_round_trips() {
+    let chunks: Vec<u32> = (7..7 + MAX_CHUNK_LIST_LENGTH as u32).collect();
+    let bytes = chunk_list(chunks.clone()).serialize(ProtocolVersion::V2);
+
+    let decoded = ChunkList::new(&bytes, bytes.len(), ProtocolVersion::V2).unwrap();
+    assert_eq!(decoded.chunks, chunks);
+}
+
+#[test]
+#[should_panic(expected = "longer than")]
+fn lists_longer_than_decoders_accept_are_not_serialized() {
+    let chunks: Vec<u32> = (0..=MAX_CHUNK_LIST_LENGTH as u32).collect();
+
+    chunk_list(chunks).serialize(ProtocolVersion::V2);
+}
+
+#[test]
+fn unsorted_chunks_keep_an_explicit_list() {
+    let list = chunk_list(vec![3, 1, 2, 2]);
+
+    assert_eq!(
+        list.encoding(),
+        (ProtocolVersion::V1, ChunkListEncoding::Explicit)
+    );
+}
+
+#[test]
+fn version_2_fragment_request_uses_compact_fragment_list() {
+    let fragments: Vec<u16> = (0..500).collect();
+    let request = FragmentRequestInfo::from_fragments(ContentId::default(), 1 << 20, fragments);
+
+    assert!(request.serialize().len() < 32);
+    match round_trip(&Message::GetFragments(request)) {
+        Message::GetFragments(data) => {
+            assert_eq!(data.fragments.chunks, (0..500).collect::<Vec<u32>>())
+        }
+        _ => panic!("Expected GetFragments"),
+    }
+}
+
+#[test]
+fn rejects_unknown_chunk_list_encoding() {
+    let bytes = [2, 4, 0, 7];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::UnknownEncoding(7))
+    );
+}
+
+#[test]
+fn rejects_inverted_range() {
+    let bytes = [2, 4, 0, 1, 0, 0, 0, 1, 0, 0, 0, 9, 0, 0, 0, 3];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::InvalidRange { start: 9, end: 3 })
+    );
+}
+
+#[test]
+fn rejects_range_expanding_past_the_limit() {
+    let bytes = [2, 4, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 255, 255, 255, 255];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::TooManyChunks {
+            max: MAX_CHUNK_LIST_LENGTH
+        })
+    );
+}
+
+#[test]
+fn rejects_bitfield_expanding_past_the_limit() {
+    let bytes = [2, 4, 0, 2, 0, 0, 0, 0, 255, 255, 255, 255];
+
+    assert_eq!(
+        Message::new(&bytes, bytes.len()).err(),
+        Some(ProtocolError::TooManyChunks {
+            max: MAX_CHUNK_LIST_LENGTH
+        })
+    );
+}
+
+#[test]
+fn join_and_bye_round_trip() {
+    assert!(matches!(round_trip(&Message::Join), Message::Join));
+    assert!(matches!(round_trip(&Message::Bye), Message::Bye));
+}
+
+#[test]
+fn peer_exchange_round_trip() {
+    let peers: Vec<SocketAddr> = vec![
+        "127.0.0.1:5001".parse().unwrap(),
+        "[::1]:5002".parse().unwrap(),
+    ];
+    let message = Message::PeerExchange(PeerExchangeInfo::from_peers(peers.clone()));
+
+    match round_trip(&message) {
+        Message::PeerExchange(data) => {
+            assert_eq!(data.message_type, MessageType::PeerExchange);
+            assert_eq!(data.peers, peers);
+        }
+        _ => panic!("Expected PeerExchange"),
+    }
+}
+
+#[test]
+fn rejects_peer_exchange_with_missing_addresses() {
+    let mut bytes =
+        PeerExchangeInfo::from_peers(vec!["127.0.0.1:5001".parse().unwrap()]).serialize();
+    bytes[3] = 2;
+
+    assert!(matches!(
+        Message::new(&bytes, bytes.len()),
+        Err(ProtocolError::Truncated { .. })
+    ));
+}
+
+#[test]
+fn ping_and_pong_round_trip() {
+    match round_trip(&Message::Ping(KeepaliveInfo::ping(0x0102_0304))) {
+        Message::Ping(data) => {
+            assert_eq!(data.message_type, MessageType::Ping);
+            assert_eq!(data.nonce, 0x0102_0304);
+        }
+        _ => panic!("Expected Ping"),
+    }
+
+    match round_trip(&Message::Pong(KeepaliveInfo::pong(42))) {
+        Message::Pong(data) => {
+            assert_eq!(data.message_type, MessageType::Pong);
+            assert_eq!(data.nonce, 42);
+        }
+        _ => panic!("Expected Pong"),
+    }
+}
+
+#[test]
+fn announce_and_tracker_query_round_trip() {
+    for message_type in [
+        MessageType::Announce,
+        MessageType::AnnounceMore,
+        MessageType::TrackerQuery,
+    ] {
+        let message = ChunkListMessage::from_chunks(message_type, content("sintel"), vec![1, 2, 3]);
+        let bytes = message.serialize();
+
+        match Message::new(&bytes, bytes.len()).unwrap() {
+            Message::Announce(data) | Message::AnnounceMore(data) | Message::TrackerQuery(data) => {
+                assert_eq!(data.message_type, message_type);
+                assert_eq!(data.content, content("sintel"));
+                assert_eq!(data.chunk_list.chunks, vec![1, 2, 3]);
+            }
+            _ => panic!("Expected Announce or TrackerQuery"),
+        }
+    }
+}
+
+#[test]
+fn tracker_reply_round_trip() {
+    let address: SocketAddr = "[::1]:6001".parse().unwrap();
+    let message = Message::TrackerReply(ProviderInfo::from_chunks(
+        content("sintel"),
+        address,
+        (1..=500).collect(),
+    ));
+
+    match round_trip(&message) {
+        Message::TrackerReply(data) => {
+            assert_eq!(data.message_type, MessageType::TrackerReply);
+            assert_eq!(data.content, content("sintel"));
+            assert_eq!(data.address, address);
+            assert_eq!(data.chunk_list.chunks, (1..=500).collect::<Vec<_>>());
+        }
+        _ => panic!("Expected TrackerReply"),
+    }
+}
+
+#[test]
+fn find_node_and_find_providers_round_trip() {
+    match round_trip(&Message::FindNode(LookupInfo::find_node(Some(17), 0xfeed))) {
+        Message::FindNode(data) => {
+            assert_eq!(data.message_type, MessageType::FindNode);
+            assert_eq!(data.sender, Some(17));
+            assert_eq!(data.target, 0xfeed);
+        }
+        _ => panic!("Expected FindNode"),
+    }
+
+    match round_trip(&Message::FindProviders(LookupInfo::find_providers(
+        None,
+        u64::MAX,
+    ))) {
+        Message::FindProviders(data) => {
+            assert_eq!(data.message_type, MessageType::FindProviders);
+            assert_eq!(data.sender, None);
+            assert_eq!(data.target, u64::MAX);
+        }
+        _ => panic!("Expected FindProviders"),
+    }
+}
+
+#[test]
+fn nodes_round_trip() {
+    let providers: Vec<SocketAddr> = vec!["127.0.0.1:6001".parse().unwrap()];
+    let nodes = vec![
+        Contact {
+            id: 1,
+            address: "127.0.0.1:6002".parse().unwrap(),
+        },
+        Contact {
+            id: u64::MAX - 1,
+            address: "[::1]:6003".parse().unwrap(),
+        },
+    ];
+    let message = Message::Nodes(NodesInfo::from_nodes(
+        Some(9),
+        42,
+        providers.clone(),
+        nodes.clone(),
+    ));
+
+    match round_trip(&message) {
+        Message::Nodes(data) => {
+            assert_eq!(data.message_type, MessageType::Nodes);
+            assert_eq!(data.sender, Some(9));
+            assert_eq!(data.target, 42);
+            assert_eq!(data.providers, providers);
+            assert_eq!(data.nodes, nodes);
+        }
+        _ => panic!("Expected Nodes"),
+    }
+}
+
+#[test]
+fn add_provider_round_trip() {
+    let message = Message::AddProvider(AddProviderInfo::from_keys(Some(3), vec![5, 1 << 40]));
+
+    match round_trip(&message) {
+        Message::AddProvider(data) => {
+            assert_eq!(data.message_type, MessageType::AddProvider);
+            assert_eq!(data.sender, Some(3));
+            assert_eq!(data.keys, vec![5, 1 << 40]);
+        }
+        _ => panic!("Expected AddProvider"),
+    }
+}
+
+#[test]
+fn rejects_add_provider_with_missing_keys() {
+    let mut bytes = AddProviderInfo::from_keys(None, vec![5]).serialize();
+    bytes.truncate(bytes.len() - 1);
+
+    assert!(matches!(
+        Message::new(&bytes, bytes.len()),
+        Err(ProtocolError::LengthMismatch { .. })
+    ));
+}
+
+#[test]
+fn chunk_keys_are_stable() {
+    // Nodes built separately must agree on where a chunk lives in the DHT.
+    assert_eq!(
+        common::chunk_key(&content("sintel"), 7),
+        0x4438_90f9_e26a_a13f
+    );
+    assert_ne!(
+        common::chunk_key(&content("sintel"), 7),
+        common::chunk_key(&ContentId::default(), 7)
+    );
+}
@@ -0,0 +1,87 @@
+use common::{ChunkListMessage, ContentId, MessageType};
+use std::{
+    collections::BTreeMap,
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use crate::catalogue::Catalogue;
+use crate::chunk_manager::ChunkId;
+use crate::error_stats::ErrorStats;
+use crate::peer_config::PeerConfig;
+
+/// Keeps every Announce within one datagram, whatever the encoding of its chunk list.
+const MAX_CHUNKS_PER_ANNOUNCE: usize = 8 * 1024;
+
+/// Starts the thread announcing the chunks of the current catalogue to `config.tracker`, if
+/// set, on startup and then every `config.announce_interval`. Every content is announced
+/// separately, in an Announce followed by as many AnnounceMore as its chunks need, and
+/// contents dropped by a reload are withdrawn with an empty Announce.
+pub fn spawn(
+    catalogue: Arc<Catalogue>,
+    udp_socket: Arc<UdpSocket>,
+    config: Arc<PeerConfig>,
+    error_stats: Arc<ErrorStats>,
+) -> Option<JoinHandle<()>> {
+    let tracker = config.tracker?;
+
+    Some(thread::spawn(move || {
+        let mut announced: Vec<ContentId> = Vec::new();
+
+        loop {
+            let mut contents: BTreeMap<ContentId, Vec<ChunkId>> = BTreeMap::new();
+            for key in catalogue.current().list() {
+                contents.entry(key.content).or_default().push(key.chunk_id);
+            }
+
+            for content in announced.iter().filter(|c| !contents.contains_key(c)) {
+                announce(
+                    &udp_socket,
+                    tracker,
+                    content.clone(),
+                    Vec::new(),
+                    &error_stats,
+                );
+            }
+            announced = contents.keys().cloned().collect();
+
+            for (content, chunks) in contents {
+                announce(&udp_socket, tracker, content, chunks, &error_stats);
+            }
+
+            thread::sleep(config.announce_interval);
+        }
+    }))
+}
+
+fn announce(
+    udp_socket: &UdpSocket,
+    tracker: SocketAddr,
+    content: ContentId,
+    chunks: Vec<ChunkId>,
+    error_stats: &ErrorStats,
+) {
+    println!(
+        "Announcing {} chunks of content {} to tracker {}",
+        chunks.len(),
+        content,
+        tracker
+    );
+
+    // The first batch replaces the previous announcement, so an empty list still needs one.
+    let mut batches: Vec<&[ChunkId]> = chunks.chunks(MAX_CHUNKS_PER_ANNOUNCE).collect();
+    if batches.is_empty() {
+        batches.push(&[]);
+    }
+
+    for (index, batch) in batches.into_iter().enumerate() {
+        let message_type = if index == 0 {
+            MessageType::Announce
+        } else {
+            MessageType::AnnounceMore
+        };
+        let message = ChunkListMessage::from_chunks(message_type, content.clone(), batch.to_vec());
+        crate::send_or_log(udp_socket, &message.serialize(), tracker, error_stats);
+    }
+}
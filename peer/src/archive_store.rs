@@ -0,0 +1,228 @@
+use common::ContentId;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::chunk_manager::{Chunk, ChunkKey};
+use crate::chunk_store::ChunkStore;
+
+const MAGIC: &[u8; 8] = b"P2PCHNK3";
+
+/// Size of the fixed part of the header preceding every chunk: the chunk ID (u32) and the
+/// chunk length (u64).
+const RECORD_HEADER_SIZE: usize = 4 + 8;
+
+/// All chunks packed in a single file: a magic number followed by one record per chunk, each
+/// made of the content name (a one-byte length and the UTF-8 name), a big-endian chunk ID, a
+/// big-endian u64 length and the chunk bytes. The file is scanned once to build an index of
+/// offsets; inserts append a new record, and a later record for the same chunk replaces the
+/// earlier ones.
+pub struct ArchiveStore {
+    file: Mutex<File>,
+    index: Mutex<HashMap<ChunkKey, (u64, u64)>>,
+}
+
+impl ArchiveStore {
+    /// Opens the archive at `path`, creating an empty one if it does not exist.
+    pub fn open(path: &Path) -> Result<ArchiveStore, String> {
+        let describe =
+            |err: io::Error| format!("Unable to open archive {}: {}", path.display(), err);
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(describe)?;
+
+        if file.metadata().map_err(describe)?.len() == 0 {
+            file.write_all(MAGIC).map_err(describe)?;
+        }
+
+        let index = ArchiveStore::build_index(&mut file).map_err(describe)?;
+        println!("Archive {} holds {} chunks", path.display(), index.len());
+
+        Ok(ArchiveStore {
+            file: Mutex::new(file),
+            index: Mutex::new(index),
+        })
+    }
+
+    fn build_index(file: &mut File) -> io::Result<HashMap<ChunkKey, (u64, u64)>> {
+        let file_length = file.metadata()?.len();
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a chunk archive",
+            ));
+        }
+
+        let mut index = HashMap::new();
+        let mut position = MAGIC.len() as u64;
+        while position < file_length {
+            let mut content_length = [0; 1];
+            file.read_exact(&mut content_length)?;
+            let mut content = vec![0; content_length[0] as usize];
+            file.read_exact(&mut content)?;
+            let content = String::from_utf8(content)
+                .ok()
+                .and_then(|content| ContentId::new(&content).ok())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid content name")
+                })?;
+
+            let mut header = [0; RECORD_HEADER_SIZE];
+            file.read_exact(&mut header)?;
+
+            let chunk_id = u32::from_be_bytes(header[..4].try_into().unwrap());
+            let length = u64::from_be_bytes(header[4..].try_into().unwrap());
+            let data_offset = file.stream_position()?;
+            let data_end = data_offset.checked_add(length).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid chunk record length")
+            })?;
+            if data_end > file_length {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated chunk record",
+                ));
+            }
+
+            index.insert(ChunkKey::new(content, chunk_id), (data_offset, length));
+            position = file.seek(SeekFrom::Start(data_end))?;
+        }
+
+        Ok(index)
+    }
+}
+
+impl ChunkStore for ArchiveStore {
+    fn get(&self, key: &ChunkKey) -> io::Result<Option<Arc<Chunk>>> {
+        let (offset, length) = match self.index.lock().unwrap().get(key) {
+            Some(&location) => location,
+            None => return Ok(None),
+        };
+
+        let mut chunk = vec![0; length as usize];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut chunk)?;
+
+        Ok(Some(Arc::new(chunk)))
+    }
+
+    fn contains(&self, key: &ChunkKey) -> bool {
+        self.index.lock().unwrap().contains_key(key)
+    }
+
+    fn list(&self) -> Vec<ChunkKey> {
+        let mut keys: Vec<ChunkKey> = self.index.lock().unwrap().keys().cloned().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    fn insert(&self, key: ChunkKey, chunk: Chunk) -> io::Result<()> {
+        let content = key.content.as_str().as_bytes();
+        let header_size = 1 + content.len() + RECORD_HEADER_SIZE;
+
+        let mut record = Vec::with_capacity(header_size + chunk.len());
+        record.push(content.len() as u8);
+        record.extend(content.iter());
+        record.extend(key.chunk_id.to_be_bytes().iter());
+        record.extend((chunk.len() as u64).to_be_bytes().iter());
+        record.extend(chunk.iter());
+
+        let mut file = self.file.lock().unwrap();
+        let position = file.seek(SeekFrom::End(0))?;
+        file.write_all(&record)?;
+
+        self.index
+            .lock()
+            .unwrap()
+            .insert(key, (position + header_size as u64, chunk.len() as u64));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn key(chunk_id: u32) -> ChunkKey {
+        ChunkKey::new(ContentId::default(), chunk_id)
+    }
+
+    #[test]
+    fn later_records_replace_earlier_ones() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("chunks.pack");
+        let archive = ArchiveStore::open(&path).unwrap();
+        archive.insert(key(1), vec![1; 8]).unwrap();
+        archive.insert(key(2), vec![2; 8]).unwrap();
+        archive.insert(key(1), vec![3; 4]).unwrap();
+        assert_eq!(archive.get(&key(1)).unwrap().as_deref(), Some(&vec![3; 4]));
+
+        let reopened = ArchiveStore::open(&path).unwrap();
+
+        assert_eq!(reopened.list(), vec![key(1), key(2)]);
+        assert_eq!(reopened.get(&key(1)).unwrap().as_deref(), Some(&vec![3; 4]));
+        assert_eq!(reopened.get(&key(2)).unwrap().as_deref(), Some(&vec![2; 8]));
+    }
+
+    #[test]
+    fn truncated_records_are_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("chunks.pack");
+        ArchiveStore::open(&path)
+            .unwrap()
+            .insert(key(1), vec![1; 8])
+            .unwrap();
+        let length = fs::metadata(&path).unwrap().len();
+
+        // Cut into the chunk bytes, then into the record header.
+        for cut in &[1, 8 + 1] {
+            let file = OpenOptions::new().write(true).open(&path).unwrap();
+            file.set_len(length - cut).unwrap();
+
+            assert!(ArchiveStore::open(&path).is_err());
+        }
+    }
+
+    #[test]
+    fn record_lengths_overflowing_the_offset_are_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("chunks.pack");
+        ArchiveStore::open(&path)
+            .unwrap()
+            .insert(key(1), vec![1; 8])
+            .unwrap();
+        let length = fs::metadata(&path).unwrap().len();
+
+        // Overwrite the length of the record, just before its 8 chunk bytes.
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(length - 8 - 8)).unwrap();
+        file.write_all(&u64::MAX.to_be_bytes()).unwrap();
+
+        let err = ArchiveStore::open(&path).err().unwrap();
+        assert!(err.ends_with("invalid chunk record length"), "{}", err);
+    }
+
+    #[test]
+    fn files_without_the_magic_number_are_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("chunks.pack");
+        fs::write(&path, b"not an archive").unwrap();
+
+        assert!(ArchiveStore::open(&path).is_err());
+    }
+}
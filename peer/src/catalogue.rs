@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::chunk_manager::ChunkManager;
+use crate::peer_config::PeerConfig;
+
+/// How often the watcher wakes up to look for a SIGHUP.
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The chunk manager currently in use. A reload swaps in a whole new manager; requests that
+/// already took the previous one keep using it until they finish.
+pub struct Catalogue {
+    current: RwLock<Arc<ChunkManager>>,
+}
+
+impl Catalogue {
+    pub fn new(chunk_manager: ChunkManager) -> Catalogue {
+        Catalogue {
+            current: RwLock::new(Arc::new(chunk_manager)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ChunkManager> {
+        self.current.read().unwrap().clone()
+    }
+
+    fn replace(&self, chunk_manager: ChunkManager) {
+        *self.current.write().unwrap() = Arc::new(chunk_manager);
+    }
+}
+
+/// Starts the thread reloading the catalogue when the peer receives a SIGHUP or, every
+/// `config.reload_interval`, when the modification time of the store or of a watched path
+/// changed. A store that fails to open is reported and the previous catalogue is kept, and
+/// stores that cannot be rebuilt from disk are never reloaded.
+pub fn spawn_watcher(
+    catalogue: Arc<Catalogue>,
+    config: Arc<PeerConfig>,
+) -> Result<JoinHandle<()>, String> {
+    let reload_requested = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, reload_requested.clone())
+        .map_err(|err| format!("Unable to listen for SIGHUP: {}", err))?;
+
+    Ok(thread::spawn(move || {
+        let mut modified = modification_times(&config);
+        let mut last_poll = Instant::now();
+
+        loop {
+            thread::sleep(SIGNAL_CHECK_INTERVAL);
+
+            let mut reload = reload_requested.swap(false, Ordering::Relaxed);
+            if let (Some(interval), true) = (config.reload_interval, config.can_reload_store()) {
+                if last_poll.elapsed() >= interval {
+                    last_poll = Instant::now();
+
+                    let current = modification_times(&config);
+                    reload |= current != modified;
+                    modified = current;
+                }
+            }
+
+            if reload {
+                self::reload(&catalogue, &config);
+            }
+        }
+    }))
+}
+
+fn reload(catalogue: &Catalogue, config: &PeerConfig) {
+    if !config.can_reload_store() {
+        println!("Ignoring reload: the store cannot be rebuilt from disk");
+        return;
+    }
+
+    match config
+        .store
+        .open(Path::new(&config.store_path), config.manifest_mode)
+    {
+        Ok(store) => {
+            println!("Reloaded catalogue: hosting {} chunks", store.list().len());
+            catalogue.replace(ChunkManager::new(store, config.cache_size));
+        }
+        Err(err) => eprintln!(
+            "Unable to reload catalogue, keeping the previous one: {}",
+            err
+        ),
+    }
+}
+
+fn modification_times(config: &PeerConfig) -> Vec<Option<SystemTime>> {
+    std::iter::once(&config.store_path)
+        .chain(config.watched_paths.iter())
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_manager::ChunkKey;
+    use crate::chunk_store::StoreKind;
+    use crate::manifest::ParseMode;
+    use common::ContentId;
+    use std::net::SocketAddr;
+
+    fn config(store: StoreKind, store_path: &Path) -> PeerConfig {
+        let mut config = PeerConfig::with_defaults(
+            SocketAddr::from(([127, 0, 0, 1], 7000)),
+            store_path.to_str().unwrap().to_string(),
+        );
+        config.store = store;
+        config
+    }
+
+    fn listed(catalogue: &Catalogue) -> Vec<u32> {
+        catalogue
+            .current()
+            .list()
+            .iter()
+            .map(|key| key.chunk_id)
+            .collect()
+    }
+
+    #[test]
+    fn reload_picks_up_new_chunks() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("1.m4s"), b"1").unwrap();
+        let config = config(StoreKind::Directory, directory.path());
+        let catalogue = Catalogue::new(ChunkManager::new(
+            StoreKind::Directory
+                .open(directory.path(), config.manifest_mode)
+                .unwrap(),
+            config.cache_size,
+        ));
+        let previous = catalogue.current();
+
+        fs::write(directory.path().join("2.m4s"), b"2").unwrap();
+        reload(&catalogue, &config);
+
+        assert_eq!(listed(&catalogue), vec![1, 2]);
+        assert_eq!(previous.list().len(), 1);
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_previous_catalogue() {
+        let directory = tempfile::tempdir().unwrap();
+        let kv_file = directory.path().join("key-values");
+        fs::write(directory.path().join("a.m4s"), b"a").unwrap();
+        fs::write(&kv_file, "1: a.m4s\n").unwrap();
+        let config = config(StoreKind::KvFile, &kv_file);
+        let catalogue = Catalogue::new(ChunkManager::new(
+            StoreKind::KvFile
+                .open(&kv_file, config.manifest_mode)
+                .unwrap(),
+            config.cache_size,
+        ));
+
+        fs::write(&kv_file, "1: a.m4s\nbroken line\n").unwrap();
+        reload(&catalogue, &config);
+
+        assert_eq!(listed(&catalogue), vec![1]);
+    }
+
+    #[test]
+    fn stores_that_cannot_be_rebuilt_are_not_reloaded() {
+        let directory = tempfile::tempdir().unwrap();
+        let kv_file = directory.path().join("key-values");
+        fs::write(&kv_file, "").unwrap();
+        let store = StoreKind::Memory.open(&kv_file, ParseMode::Strict).unwrap();
+        store
+            .insert(ChunkKey::new(ContentId::default(), 3), vec![3])
+            .unwrap();
+        let memory = config(StoreKind::Memory, &kv_file);
+        let catalogue = Catalogue::new(ChunkManager::new(store, memory.cache_size));
+
+        reload(&catalogue, &memory);
+        assert_eq!(listed(&catalogue), vec![3]);
+
+        let mut imported = config(StoreKind::Directory, directory.path());
+        imported.import_path = Some(kv_file.to_str().unwrap().to_string());
+        reload(&catalogue, &imported);
+        assert_eq!(listed(&catalogue), vec![3]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::chunk_manager::{Chunk, ChunkKey};
+
+/// Least-recently-used cache of chunk contents, bounded by the total size of the chunks it
+/// holds.
+pub struct ChunkCache {
+    capacity: usize,
+    size: usize,
+    clock: u64,
+    entries: HashMap<ChunkKey, CacheEntry>,
+    /// The cached keys by the time they were last used, oldest first.
+    recency: BTreeMap<u64, ChunkKey>,
+}
+
+struct CacheEntry {
+    chunk: Arc<Chunk>,
+    last_used: u64,
+}
+
+impl ChunkCache {
+    pub fn new(capacity: usize) -> ChunkCache {
+        ChunkCache {
+            capacity,
+            size: 0,
+            clock: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &ChunkKey) -> Option<Arc<Chunk>> {
+        let entry = self.entries.get_mut(key)?;
+
+        self.clock += 1;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(self.clock, key.clone());
+        entry.last_used = self.clock;
+
+        Some(entry.chunk.clone())
+    }
+
+    /// Caches `chunk`, evicting the least recently used chunks to make room for it. Chunks
+    /// larger than the whole cache are not kept.
+    pub fn insert(&mut self, key: ChunkKey, chunk: Arc<Chunk>) {
+        if chunk.len() > self.capacity {
+            return;
+        }
+
+        self.remove(&key);
+        while self.size + chunk.len() > self.capacity {
+            match self.recency.pop_first() {
+                Some((_last_used, evicted)) => self.remove(&evicted),
+                None => break,
+            }
+        }
+
+        self.clock += 1;
+        self.size += chunk.len();
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                chunk,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &ChunkKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.size -= entry.chunk.len();
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ContentId;
+
+    fn key(chunk_id: u32) -> ChunkKey {
+        ChunkKey::new(ContentId::default(), chunk_id)
+    }
+
+    fn chunk(size: usize) -> Arc<Chunk> {
+        Arc::new(vec![0; size])
+    }
+
+    #[test]
+    fn evicts_least_recently_used_chunks_first() {
+        let mut cache = ChunkCache::new(30);
+        cache.insert(key(1), chunk(10));
+        cache.insert(key(2), chunk(10));
+        cache.insert(key(3), chunk(10));
+        cache.get(&key(1));
+
+        cache.insert(key(4), chunk(10));
+
+        assert!(cache.get(&key(2)).is_none());
+        assert!(cache.get(&key(1)).is_some());
+        assert!(cache.get(&key(3)).is_some());
+        assert!(cache.get(&key(4)).is_some());
+    }
+
+    #[test]
+    fn evicts_as_many_chunks_as_needed() {
+        let mut cache = ChunkCache::new(30);
+        cache.insert(key(1), chunk(10));
+        cache.insert(key(2), chunk(10));
+        cache.insert(key(3), chunk(10));
+
+        cache.insert(key(4), chunk(25));
+
+        assert_eq!(cache.size, 25);
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.get(&key(4)).is_some());
+    }
+
+    #[test]
+    fn replacing_a_chunk_updates_the_size() {
+        let mut cache = ChunkCache::new(30);
+        cache.insert(key(1), chunk(10));
+        cache.insert(key(1), chunk(20));
+
+        assert_eq!(cache.size, 20);
+        assert_eq!(cache.get(&key(1)).map(|chunk| chunk.len()), Some(20));
+        assert_eq!(cache.recency.len(), 1);
+    }
+
+    #[test]
+    fn chunks_larger_than_the_cache_are_not_kept() {
+        let mut cache = ChunkCache::new(30);
+        cache.insert(key(1), chunk(10));
+
+        cache.insert(key(2), chunk(31));
+
+        assert!(cache.get(&key(2)).is_none());
+        assert!(cache.get(&key(1)).is_some());
+        assert_eq!(cache.size, 10);
+    }
+}
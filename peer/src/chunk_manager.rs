@@ -1,47 +1,81 @@
-use std::{collections::HashMap, fs};
+use common::ContentId;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
 
-use crate::peer_config::PeerConfig;
+use crate::chunk_cache::ChunkCache;
+use crate::chunk_store::ChunkStore;
 
-pub type ChunkId = u16;
+pub use common::ChunkId;
 pub type Chunk = Vec<u8>;
+
+/// Identifies a chunk among all the contents hosted by the peer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkKey {
+    pub content: ContentId,
+    pub chunk_id: ChunkId,
+}
+
+impl ChunkKey {
+    pub fn new(content: ContentId, chunk_id: ChunkId) -> ChunkKey {
+        ChunkKey { content, chunk_id }
+    }
+}
+
+impl fmt::Display for ChunkKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.content.is_default() {
+            write!(f, "{}", self.chunk_id)
+        } else {
+            write!(f, "{}/{}", self.content, self.chunk_id)
+        }
+    }
+}
+
+/// Gives access to every chunk hosted by the peer. Chunks are only read from the store when
+/// requested, and the most recently used ones are kept in a memory cache of bounded size.
 pub struct ChunkManager {
-    map: HashMap<ChunkId, Chunk>,
+    store: Box<dyn ChunkStore>,
+    cache: Mutex<ChunkCache>,
 }
 
 impl ChunkManager {
-    pub fn new(config: &PeerConfig) -> ChunkManager {
-        let kv_file_contents =
-            fs::read_to_string(&config.kv_file_path).expect("Unable to open key-value file");
-
-        let mut map: HashMap<ChunkId, Chunk> = HashMap::new();
-        for line in kv_file_contents.lines() {
-            let mut split = line.split(": ");
-            let key = split
-                .next()
-                .expect("Key-value file line has unknown format.")
-                .parse()
-                .expect("Key is not a number");
-            let path = split
-                .next()
-                .expect("Key-value file line has unknown format.")
-                .to_string();
-
-            println!("Path: {}", path);
-
-            let content = fs::read(path).expect("Unable to read chunk file");
-            println!("Read {} bytes", content.len());
-
-            map.insert(key, content);
+    pub fn new(store: Box<dyn ChunkStore>, cache_size: usize) -> ChunkManager {
+        ChunkManager {
+            store,
+            cache: Mutex::new(ChunkCache::new(cache_size)),
         }
+    }
 
-        ChunkManager { map }
+    pub fn contains(&self, key: &ChunkKey) -> bool {
+        self.store.contains(key)
     }
 
-    pub fn contains(&self, key: &ChunkId) -> bool {
-        self.map.contains_key(key)
+    /// Every chunk in the store, sorted.
+    pub fn list(&self) -> Vec<ChunkKey> {
+        self.store.list()
     }
 
-    pub fn get(&self, key: &ChunkId) -> Option<&Chunk> {
-        self.map.get(key)
+    /// Returns the contents of a chunk, reading it from the store if it is not cached.
+    pub fn get(&self, key: &ChunkKey) -> Option<Arc<Chunk>> {
+        if let Some(chunk) = self.cache.lock().unwrap().get(key) {
+            return Some(chunk);
+        }
+
+        let chunk = match self.store.get(key) {
+            Ok(chunk) => chunk?,
+            Err(err) => {
+                eprintln!("Unable to read chunk {}: {}", key, err);
+                return None;
+            }
+        };
+        println!("Read {} bytes of chunk {}", chunk.len(), key);
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key.clone(), chunk.clone());
+        Some(chunk)
     }
 }
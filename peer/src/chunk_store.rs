@@ -0,0 +1,189 @@
+use std::{io, path::Path, str::FromStr, sync::Arc};
+
+use crate::archive_store::ArchiveStore;
+use crate::chunk_manager::{Chunk, ChunkKey};
+use crate::directory_store::DirectoryStore;
+use crate::kv_file_store::KvFileStore;
+use crate::manifest::ParseMode;
+use crate::memory_store::MemoryStore;
+
+/// Where the chunks hosted by a peer are kept. Implementations must be safe to share between
+/// the threads serving clients.
+pub trait ChunkStore: Send + Sync {
+    /// Reads a chunk, returning `None` if the store does not have it.
+    fn get(&self, key: &ChunkKey) -> io::Result<Option<Arc<Chunk>>>;
+
+    fn contains(&self, key: &ChunkKey) -> bool;
+
+    /// Every chunk in the store, in ascending order.
+    fn list(&self) -> Vec<ChunkKey>;
+
+    /// Adds a chunk to the store, replacing any chunk with the same key.
+    fn insert(&self, key: ChunkKey, chunk: Chunk) -> io::Result<()>;
+}
+
+/// The storage backends a peer can be started with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreKind {
+    KvFile,
+    Directory,
+    Archive,
+    Memory,
+}
+
+impl StoreKind {
+    /// Opens a store of this kind at `path`. The memory store is filled with the chunks
+    /// listed in the key-value file at `path`. `mode` applies to key-value files only.
+    pub fn open(self, path: &Path, mode: ParseMode) -> Result<Box<dyn ChunkStore>, String> {
+        Ok(match self {
+            StoreKind::KvFile => Box::new(KvFileStore::open(path, mode)?),
+            StoreKind::Directory => Box::new(DirectoryStore::open(path)?),
+            StoreKind::Archive => Box::new(ArchiveStore::open(path)?),
+            StoreKind::Memory => {
+                let store = MemoryStore::default();
+                import_chunks(&store, path, mode)?;
+                Box::new(store)
+            }
+        })
+    }
+}
+
+/// Copies every chunk listed in the key-value file at `kv_file_path` into `store`.
+pub fn import_chunks(
+    store: &dyn ChunkStore,
+    kv_file_path: &Path,
+    mode: ParseMode,
+) -> Result<(), String> {
+    let source = KvFileStore::open(kv_file_path, mode)?;
+
+    for key in source.list() {
+        let chunk = source
+            .get(&key)
+            .map_err(|err| format!("Unable to read chunk {}: {}", key, err))?
+            .ok_or_else(|| format!("Chunk {} disappeared while importing", key))?;
+
+        store
+            .insert(key.clone(), chunk.to_vec())
+            .map_err(|err| format!("Unable to store chunk {}: {}", key, err))?;
+    }
+
+    Ok(())
+}
+
+impl FromStr for StoreKind {
+    type Err = String;
+
+    /// Parses `kv`, `dir`, `archive` or `memory`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "kv" => Ok(StoreKind::KvFile),
+            "dir" => Ok(StoreKind::Directory),
+            "archive" => Ok(StoreKind::Archive),
+            "memory" => Ok(StoreKind::Memory),
+            _ => Err(format!("Unknown store '{}'", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ContentId;
+    use std::fs;
+
+    fn keys() -> Vec<ChunkKey> {
+        vec![
+            ChunkKey::new(ContentId::default(), 1),
+            ChunkKey::new(ContentId::new("sintel").unwrap(), 1),
+            ChunkKey::new(ContentId::new("sintel/720p").unwrap(), 70_000),
+        ]
+    }
+
+    /// Inserts a chunk per key, reopens the store and reads every chunk back.
+    fn round_trip(kind: StoreKind, path: &Path) {
+        let store = kind.open(path, ParseMode::Strict).unwrap();
+        for (index, key) in keys().into_iter().enumerate() {
+            store.insert(key, vec![index as u8; 10 + index]).unwrap();
+        }
+
+        let reopened = kind.open(path, ParseMode::Strict).unwrap();
+
+        let mut expected = keys();
+        expected.sort_unstable();
+        assert_eq!(reopened.list(), expected);
+        for (index, key) in keys().iter().enumerate() {
+            assert!(reopened.contains(key));
+            assert_eq!(
+                reopened.get(key).unwrap().as_deref(),
+                Some(&vec![index as u8; 10 + index])
+            );
+        }
+        assert_eq!(
+            reopened
+                .get(&ChunkKey::new(ContentId::default(), 2))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn kv_file_store_keeps_inserted_chunks() {
+        let directory = tempfile::tempdir().unwrap();
+        let kv_file = directory.path().join("key-values");
+        fs::write(&kv_file, "").unwrap();
+
+        round_trip(StoreKind::KvFile, &kv_file);
+    }
+
+    #[test]
+    fn directory_store_keeps_inserted_chunks() {
+        let directory = tempfile::tempdir().unwrap();
+
+        round_trip(StoreKind::Directory, directory.path());
+    }
+
+    #[test]
+    fn archive_store_keeps_inserted_chunks() {
+        let directory = tempfile::tempdir().unwrap();
+
+        round_trip(StoreKind::Archive, &directory.path().join("chunks.pack"));
+    }
+
+    #[test]
+    fn memory_store_is_filled_from_the_key_value_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let kv_file = directory.path().join("key-values");
+        fs::write(directory.path().join("chunk.m4s"), b"chunk").unwrap();
+        fs::write(&kv_file, "sintel/4: chunk.m4s\n").unwrap();
+
+        let store = StoreKind::Memory.open(&kv_file, ParseMode::Strict).unwrap();
+        let key = ChunkKey::new(ContentId::new("sintel").unwrap(), 4);
+        assert_eq!(store.list(), vec![key.clone()]);
+        assert_eq!(
+            store.get(&key).unwrap().as_deref(),
+            Some(&b"chunk".to_vec())
+        );
+
+        let inserted = ChunkKey::new(ContentId::default(), 5);
+        store.insert(inserted.clone(), vec![5]).unwrap();
+        assert_eq!(store.get(&inserted).unwrap().as_deref(), Some(&vec![5]));
+    }
+
+    #[test]
+    fn import_copies_every_listed_chunk() {
+        let directory = tempfile::tempdir().unwrap();
+        let kv_file = directory.path().join("key-values");
+        fs::write(directory.path().join("a.m4s"), b"a").unwrap();
+        fs::write(directory.path().join("b.m4s"), b"b").unwrap();
+        fs::write(&kv_file, "1: a.m4s\nsintel/2: b.m4s\n").unwrap();
+        let store = StoreKind::Archive
+            .open(&directory.path().join("chunks.pack"), ParseMode::Strict)
+            .unwrap();
+
+        import_chunks(store.as_ref(), &kv_file, ParseMode::Strict).unwrap();
+
+        let key = ChunkKey::new(ContentId::new("sintel").unwrap(), 2);
+        assert_eq!(store.list().len(), 2);
+        assert_eq!(store.get(&key).unwrap().as_deref(), Some(&b"b".to_vec()));
+    }
+}
@@ -0,0 +1,341 @@
+use common::{
+    AddProviderInfo, Contact, LookupInfo, Message, MessageType, NodeId, NodesInfo,
+    MAX_KEYS_PER_ANNOUNCEMENT,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::catalogue::Catalogue;
+use crate::chunk_manager::ChunkManager;
+use crate::error_stats::ErrorStats;
+use crate::neighbours::Neighbours;
+use crate::peer_config::PeerConfig;
+use crate::provider_records::ProviderRecords;
+use crate::routing_table::{RoutingTable, BUCKET_SIZE};
+
+/// Amount of nodes queried at once during a lookup.
+const ALPHA: usize = 3;
+/// Queries in flight at once over all the lookups, so that publishing many chunks does not
+/// burst thousands of datagrams.
+const MAX_QUERIES_IN_FLIGHT: usize = 256;
+/// How long a node has to answer before a lookup moves on without it.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+/// How often lookups check for timed out queries while waiting for answers.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Provider records live this many announce intervals, so a provider may miss a few
+/// republications before being forgotten.
+const RECORD_LIFETIME_INTERVALS: u32 = 4;
+/// Providers recorded per chunk key. Nodes only ever hand out `BUCKET_SIZE` of them.
+const MAX_PROVIDERS_PER_KEY: usize = 64;
+/// Provider records kept over all keys.
+const MAX_PROVIDER_RECORDS: usize = 256 * 1024;
+
+/// This peer's part of the DHT: its routing table, the provider records it keeps for others,
+/// and the lookups in progress. Requests are answered by the receiving thread, while lookups
+/// run on the DHT thread and get their answers through `pending`.
+pub struct Dht {
+    own_id: NodeId,
+    routing_table: Mutex<RoutingTable>,
+    records: Mutex<ProviderRecords>,
+    /// Lookups waiting for Nodes messages, by target.
+    pending: Mutex<HashMap<NodeId, Sender<(SocketAddr, NodesInfo)>>>,
+    udp_socket: Arc<UdpSocket>,
+    error_stats: Arc<ErrorStats>,
+}
+
+impl Dht {
+    pub fn new(
+        own_id: NodeId,
+        udp_socket: Arc<UdpSocket>,
+        config: &PeerConfig,
+        error_stats: Arc<ErrorStats>,
+    ) -> Dht {
+        Dht {
+            own_id,
+            routing_table: Mutex::new(RoutingTable::new(own_id)),
+            records: Mutex::new(ProviderRecords::new(
+                config.announce_interval * RECORD_LIFETIME_INTERVALS,
+                MAX_PROVIDERS_PER_KEY,
+                MAX_PROVIDER_RECORDS,
+            )),
+            pending: Mutex::new(HashMap::new()),
+            udp_socket,
+            error_stats,
+        }
+    }
+
+    /// Answers a FindNode or FindProviders with the closest known nodes and, for
+    /// FindProviders, up to `BUCKET_SIZE` of the recorded providers, which keeps the reply
+    /// within a datagram however popular the chunk.
+    pub fn handle_lookup(&self, data: LookupInfo, remote_address: SocketAddr) {
+        self.observe(data.sender, remote_address);
+
+        let mut nodes = self
+            .routing_table
+            .lock()
+            .unwrap()
+            .closest(data.target, BUCKET_SIZE + 1);
+        nodes.retain(|contact| contact.address != remote_address);
+        nodes.truncate(BUCKET_SIZE);
+
+        let providers = match data.message_type {
+            MessageType::FindProviders => self
+                .records
+                .lock()
+                .unwrap()
+                .providers(data.target, BUCKET_SIZE),
+            _ => Vec::new(),
+        };
+
+        let message = Message::Nodes(NodesInfo::from_nodes(
+            Some(self.own_id),
+            data.target,
+            providers,
+            nodes,
+        ));
+        crate::send_or_log(
+            &self.udp_socket,
+            &message.serialize(),
+            remote_address,
+            &self.error_stats,
+        );
+    }
+
+    /// Hands a Nodes message to the lookup waiting for it, if any.
+    pub fn handle_nodes(&self, data: NodesInfo, remote_address: SocketAddr) {
+        self.observe(data.sender, remote_address);
+
+        if let Some(lookup) = self.pending.lock().unwrap().get(&data.target) {
+            let _ = lookup.send((remote_address, data));
+        }
+    }
+
+    pub fn handle_add_provider(&self, data: AddProviderInfo, remote_address: SocketAddr) {
+        self.observe(data.sender, remote_address);
+
+        let mut records = self.records.lock().unwrap();
+        for key in data.keys {
+            records.add(key, remote_address);
+        }
+    }
+
+    /// Iterative lookups of the nodes closest to each of `targets`, all running side by side,
+    /// returning for every target the nodes that answered, closest first. Each lookup keeps up
+    /// to `ALPHA` queries in flight to the closest nodes not queried yet, and ends once the
+    /// `BUCKET_SIZE` closest nodes found have all been queried. `seeds` are queried first even
+    /// though their IDs are unknown, which is how a node with an empty routing table joins the
+    /// DHT.
+    fn lookup(&self, targets: &[NodeId], seeds: &[SocketAddr]) -> HashMap<NodeId, Vec<Contact>> {
+        let (sender, replies) = mpsc::channel();
+        let mut lookups: HashMap<NodeId, Lookup> = HashMap::new();
+        {
+            let routing_table = self.routing_table.lock().unwrap();
+            let mut pending = self.pending.lock().unwrap();
+            for &target in targets {
+                pending.insert(target, sender.clone());
+                lookups.insert(
+                    target,
+                    Lookup {
+                        shortlist: routing_table.closest(target, BUCKET_SIZE),
+                        seeds: seeds.to_vec(),
+                        queried: HashSet::new(),
+                        answered: HashSet::new(),
+                        in_flight: HashMap::new(),
+                    },
+                );
+            }
+        }
+
+        let mut in_flight = 0;
+        loop {
+            for (target, lookup) in lookups.iter_mut() {
+                let timed_out: Vec<SocketAddr> = lookup
+                    .in_flight
+                    .iter()
+                    .filter(|(_address, sent_at)| sent_at.elapsed() > QUERY_TIMEOUT)
+                    .map(|(address, _sent_at)| *address)
+                    .collect();
+                for address in timed_out {
+                    lookup.in_flight.remove(&address);
+                    lookup
+                        .shortlist
+                        .retain(|contact| contact.address != address);
+                    self.routing_table.lock().unwrap().record_failure(&address);
+                    in_flight -= 1;
+                }
+
+                while lookup.in_flight.len() < ALPHA && in_flight < MAX_QUERIES_IN_FLIGHT {
+                    let address = match lookup.next_candidate() {
+                        Some(address) => address,
+                        None => break,
+                    };
+                    let request =
+                        Message::FindNode(LookupInfo::find_node(Some(self.own_id), *target));
+                    crate::send_or_log(
+                        &self.udp_socket,
+                        &request.serialize(),
+                        address,
+                        &self.error_stats,
+                    );
+                    lookup.queried.insert(address);
+                    lookup.in_flight.insert(address, Instant::now());
+                    in_flight += 1;
+                }
+            }
+
+            // Every lookup with somebody left to ask has a query in flight.
+            if in_flight == 0 {
+                break;
+            }
+
+            let (from, reply) = match replies.recv_timeout(POLL_INTERVAL) {
+                Ok(reply) => reply,
+                Err(_) => continue,
+            };
+            let target = reply.target;
+            let lookup = match lookups.get_mut(&target) {
+                Some(lookup) => lookup,
+                None => continue,
+            };
+            if lookup.in_flight.remove(&from).is_none() {
+                continue;
+            }
+            in_flight -= 1;
+            lookup.answered.insert(from);
+
+            for node in reply.nodes {
+                let known = lookup.shortlist.iter().any(|contact| contact.id == node.id);
+                if node.id != self.own_id && !known && !lookup.queried.contains(&node.address) {
+                    lookup.shortlist.push(node);
+                }
+            }
+            lookup
+                .shortlist
+                .sort_by_key(|contact| common::distance(contact.id, target));
+            lookup.shortlist.truncate(BUCKET_SIZE);
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        lookups
+            .into_iter()
+            .map(|(target, mut lookup)| {
+                pending.remove(&target);
+                let answered = &lookup.answered;
+                lookup
+                    .shortlist
+                    .retain(|contact| answered.contains(&contact.address));
+                (target, lookup.shortlist)
+            })
+            .collect()
+    }
+
+    /// Sends every chunk of `chunk_manager` to the nodes closest to its key.
+    fn publish(&self, chunk_manager: &ChunkManager) {
+        let chunks = chunk_manager.list();
+        let keys: Vec<NodeId> = chunks
+            .iter()
+            .map(|chunk| common::chunk_key(&chunk.content, chunk.chunk_id))
+            .collect();
+
+        let mut announcements: HashMap<SocketAddr, Vec<NodeId>> = HashMap::new();
+        for (key, closest) in self.lookup(&keys, &[]) {
+            for contact in closest {
+                announcements.entry(contact.address).or_default().push(key);
+            }
+        }
+
+        println!(
+            "Publishing {} chunks to {} DHT nodes",
+            chunks.len(),
+            announcements.len()
+        );
+        for (address, keys) in announcements {
+            for batch in keys.chunks(MAX_KEYS_PER_ANNOUNCEMENT) {
+                let message = Message::AddProvider(AddProviderInfo::from_keys(
+                    Some(self.own_id),
+                    batch.to_vec(),
+                ));
+                crate::send_or_log(
+                    &self.udp_socket,
+                    &message.serialize(),
+                    address,
+                    &self.error_stats,
+                );
+            }
+        }
+    }
+
+    fn observe(&self, sender: Option<NodeId>, address: SocketAddr) {
+        if let Some(id) = sender {
+            self.routing_table
+                .lock()
+                .unwrap()
+                .observe(Contact { id, address });
+        }
+    }
+}
+
+/// One of the lookups run by `Dht::lookup`.
+struct Lookup {
+    /// Candidates, closest to the target first.
+    shortlist: Vec<Contact>,
+    /// Nodes to query before the shortlist, whose IDs are unknown.
+    seeds: Vec<SocketAddr>,
+    queried: HashSet<SocketAddr>,
+    answered: HashSet<SocketAddr>,
+    in_flight: HashMap<SocketAddr, Instant>,
+}
+
+impl Lookup {
+    /// The next node to query, if any is left.
+    fn next_candidate(&mut self) -> Option<SocketAddr> {
+        while let Some(seed) = self.seeds.pop() {
+            if !self.queried.contains(&seed) {
+                return Some(seed);
+            }
+        }
+
+        let queried = &self.queried;
+        self.shortlist
+            .iter()
+            .map(|contact| contact.address)
+            .find(|address| !queried.contains(address))
+    }
+}
+
+/// Starts the thread that, every `config.announce_interval`, looks up this node's own ID,
+/// which fills the routing table and makes the node known to its neighbourhood, and then
+/// publishes the chunks of the current catalogue.
+pub fn spawn(
+    dht: Arc<Dht>,
+    catalogue: Arc<Catalogue>,
+    neighbours: Arc<Neighbours>,
+    config: Arc<PeerConfig>,
+) -> JoinHandle<()> {
+    println!("Taking part in the DHT as node {:016x}", dht.own_id);
+
+    thread::spawn(move || loop {
+        let closest = dht
+            .lookup(&[dht.own_id], &neighbours.alive())
+            .remove(&dht.own_id)
+            .unwrap_or_default();
+        println!(
+            "DHT lookup found {} nodes close to this one, {} known in total",
+            closest.len(),
+            dht.routing_table.lock().unwrap().len()
+        );
+
+        dht.publish(&catalogue.current());
+        dht.records.lock().unwrap().expire();
+
+        thread::sleep(config.announce_interval);
+    })
+}
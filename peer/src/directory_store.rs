@@ -0,0 +1,172 @@
+use common::ContentId;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use crate::chunk_manager::{Chunk, ChunkId, ChunkKey};
+use crate::chunk_store::ChunkStore;
+
+/// Extension of the chunk files. Other files in the directory are ignored.
+const CHUNK_EXTENSION: &str = "m4s";
+
+/// Every `.m4s` file in a directory whose name ends with a chunk ID, such as
+/// `BigBuckBunny_5.m4s` or `5.m4s`. Files directly in the directory belong to the default
+/// content, and files in a subdirectory to the content named after its path, such as `sintel`
+/// or `sintel/720p`. New chunks are written as `<content>/<id>.m4s`.
+pub struct DirectoryStore {
+    directory: PathBuf,
+    paths: RwLock<HashMap<ChunkKey, PathBuf>>,
+}
+
+impl DirectoryStore {
+    pub fn open(directory: &Path) -> Result<DirectoryStore, String> {
+        let mut paths: HashMap<ChunkKey, PathBuf> = HashMap::new();
+        scan(directory, &mut Vec::new(), &mut paths)?;
+
+        Ok(DirectoryStore {
+            directory: directory.to_path_buf(),
+            paths: RwLock::new(paths),
+        })
+    }
+
+    fn content_directory(&self, content: &ContentId) -> PathBuf {
+        self.directory.join(content.as_str())
+    }
+}
+
+/// Adds the chunks found in `directory`, whose path relative to the store is `content_path`.
+fn scan(
+    directory: &Path,
+    content_path: &mut Vec<String>,
+    paths: &mut HashMap<ChunkKey, PathBuf>,
+) -> Result<(), String> {
+    let entries = fs::read_dir(directory)
+        .map_err(|err| format!("Unable to read directory {}: {}", directory.display(), err))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("Unable to read directory entry: {}", err))?
+            .path();
+
+        if path.is_dir() {
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                content_path.push(name.to_string());
+                scan(&path, content_path, paths)?;
+                content_path.pop();
+            }
+            continue;
+        }
+
+        let chunk_id = match chunk_id_from_path(&path) {
+            Some(chunk_id) => chunk_id,
+            None => continue,
+        };
+        let content = match ContentId::new(&content_path.join("/")) {
+            Ok(content) => content,
+            Err(err) => {
+                eprintln!("Skipping {}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        let key = ChunkKey::new(content, chunk_id);
+        if let Some(first) = paths.get(&key) {
+            eprintln!(
+                "Skipping {}: chunk {} is already stored in {}",
+                path.display(),
+                key,
+                first.display()
+            );
+            continue;
+        }
+
+        println!("Path: {}", path.display());
+        paths.insert(key, path);
+    }
+
+    Ok(())
+}
+
+/// Takes the chunk ID from the trailing digits of the name of a chunk file.
+fn chunk_id_from_path(path: &Path) -> Option<ChunkId> {
+    if !path.is_file() || path.extension()? != CHUNK_EXTENSION {
+        return None;
+    }
+
+    let stem = path.file_stem()?.to_str()?;
+    let digits_start = stem
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+
+    stem[digits_start..].parse().ok()
+}
+
+impl ChunkStore for DirectoryStore {
+    fn get(&self, key: &ChunkKey) -> io::Result<Option<Arc<Chunk>>> {
+        let path = match self.paths.read().unwrap().get(key) {
+            Some(path) => path.clone(),
+            None => return Ok(None),
+        };
+
+        fs::read(path).map(|content| Some(Arc::new(content)))
+    }
+
+    fn contains(&self, key: &ChunkKey) -> bool {
+        self.paths.read().unwrap().contains_key(key)
+    }
+
+    fn list(&self) -> Vec<ChunkKey> {
+        let mut keys: Vec<ChunkKey> = self.paths.read().unwrap().keys().cloned().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    fn insert(&self, key: ChunkKey, chunk: Chunk) -> io::Result<()> {
+        let directory = self.content_directory(&key.content);
+        fs::create_dir_all(&directory)?;
+
+        let path = directory.join(format!("{}.{}", key.chunk_id, CHUNK_EXTENSION));
+        fs::write(&path, chunk)?;
+
+        self.paths.write().unwrap().insert(key, path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_key(chunk_id: ChunkId) -> ChunkKey {
+        ChunkKey::new(ContentId::default(), chunk_id)
+    }
+
+    #[test]
+    fn only_chunk_files_are_stored() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("BigBuckBunny_5.m4s"), b"five").unwrap();
+        fs::write(directory.path().join("notes1.txt"), b"notes").unwrap();
+        fs::write(directory.path().join("7"), b"no extension").unwrap();
+        fs::write(directory.path().join("readme.m4s"), b"no ID").unwrap();
+
+        let store = DirectoryStore::open(directory.path()).unwrap();
+
+        assert_eq!(store.list(), vec![default_key(5)]);
+    }
+
+    #[test]
+    fn later_files_with_the_same_chunk_id_are_skipped() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("a_5.m4s"), b"first").unwrap();
+        fs::write(directory.path().join("b_5.m4s"), b"second").unwrap();
+
+        let store = DirectoryStore::open(directory.path()).unwrap();
+
+        assert_eq!(store.list(), vec![default_key(5)]);
+        let chunk = store.get(&default_key(5)).unwrap().unwrap();
+        assert!(chunk.as_slice() == b"first" || chunk.as_slice() == b"second");
+    }
+}
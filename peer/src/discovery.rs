@@ -0,0 +1,221 @@
+use common::{ChunkListMessage, HelloInfo, Message, MessageType, QueryInfo};
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::{mpsc::Receiver, Arc},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::catalogue::Catalogue;
+use crate::chunk_manager::{ChunkId, ChunkKey, ChunkManager};
+use crate::error_stats::ErrorStats;
+use crate::neighbours::Neighbours;
+use crate::peer_config::PeerConfig;
+use crate::random;
+use crate::seen_queries::SeenQueries;
+
+/// How long a query is remembered. Copies arriving later than this are handled again.
+const SEEN_QUERY_RETENTION: Duration = Duration::from_secs(30);
+/// Amount of queries remembered at once, however fast new ones arrive.
+const MAX_SEEN_QUERIES: usize = 64 * 1024;
+
+pub enum DiscoveryRequest {
+    Hello(HelloInfo, SocketAddr),
+    Query(QueryInfo, SocketAddr),
+}
+
+/// Starts the task that answers Hello and Query messages and forwards queries to the
+/// neighbours. It runs apart from chunk serving, so discovery keeps flowing while large
+/// chunks are being sent.
+pub fn spawn(
+    requests: Receiver<DiscoveryRequest>,
+    catalogue: Arc<Catalogue>,
+    neighbours: Arc<Neighbours>,
+    udp_socket: Arc<UdpSocket>,
+    config: Arc<PeerConfig>,
+    error_stats: Arc<ErrorStats>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut seen_queries = SeenQueries::new(SEEN_QUERY_RETENTION, MAX_SEEN_QUERIES);
+
+        for request in requests {
+            match request {
+                DiscoveryRequest::Hello(data, remote_address) => handle_hello(
+                    &catalogue.current(),
+                    &neighbours,
+                    &udp_socket,
+                    data,
+                    &remote_address,
+                    &config,
+                    &mut seen_queries,
+                    &error_stats,
+                ),
+                DiscoveryRequest::Query(data, remote_address) => handle_query(
+                    &catalogue.current(),
+                    &neighbours,
+                    &udp_socket,
+                    data,
+                    &config,
+                    &remote_address,
+                    &mut seen_queries,
+                    &error_stats,
+                ),
+            }
+        }
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_hello(
+    chunk_manager: &ChunkManager,
+    neighbours: &Neighbours,
+    udp_socket: &UdpSocket,
+    data: HelloInfo,
+    remote_address: &SocketAddr,
+    config: &PeerConfig,
+    seen_queries: &mut SeenQueries,
+    error_stats: &ErrorStats,
+) {
+    println!(
+        "Got hello message! Client is asking for {} chunks of content {}",
+        data.chunk_list.chunks.len(),
+        data.content
+    );
+
+    println!(
+        "{}",
+        data.chunk_list
+            .chunks
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    let mut available_chunks = Vec::new();
+    print!("Available chunks: ");
+    for chunk in &data.chunk_list.chunks {
+        if chunk_manager.contains(&ChunkKey::new(data.content.clone(), *chunk)) {
+            print!("{}", chunk);
+            available_chunks.push(*chunk);
+        }
+    }
+    println!();
+
+    if !available_chunks.is_empty() {
+        let message = ChunkListMessage::from_chunks(
+            MessageType::ChunkInfo,
+            data.content.clone(),
+            available_chunks,
+        );
+        crate::send_or_log(
+            udp_socket,
+            &message.serialize(),
+            *remote_address,
+            error_stats,
+        );
+    }
+
+    let peer_ttl = config.query_ttl_for(data.peer_ttl);
+    if peer_ttl == 0 {
+        return;
+    }
+
+    let query_id = random::random_u64() as u32;
+    seen_queries.insert(*remote_address, query_id);
+    let message = Message::Query(QueryInfo::from_chunks(
+        data.content.clone(),
+        query_id,
+        *remote_address,
+        peer_ttl,
+        data.chunk_list.clone(),
+    ));
+
+    println!("Sending query message with TTL {}", peer_ttl);
+
+    println!(
+        "{}",
+        data.chunk_list
+            .chunks
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    for peer in config.forwarding.select(&neighbours.alive(), None) {
+        crate::send_or_log(udp_socket, &message.serialize(), peer, error_stats);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_query(
+    chunk_manager: &ChunkManager,
+    neighbours: &Neighbours,
+    udp_socket: &UdpSocket,
+    data: QueryInfo,
+    config: &PeerConfig,
+    remote_address: &SocketAddr,
+    seen_queries: &mut SeenQueries,
+    error_stats: &ErrorStats,
+) {
+    println!(
+        "Got QUERY message! Client is asking for chunks of content {}: {}",
+        data.content,
+        data.chunk_info
+            .chunks
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    if !seen_queries.insert(data.address, data.query_id) {
+        println!(
+            "Dropping duplicate query {} from client {}",
+            data.query_id, data.address
+        );
+        return;
+    }
+
+    let available_chunks: Vec<ChunkId> = data
+        .chunk_info
+        .chunks
+        .iter()
+        .filter(|&&chunk| chunk_manager.contains(&ChunkKey::new(data.content.clone(), chunk)))
+        .copied()
+        .collect();
+
+    if !available_chunks.is_empty() {
+        let message = ChunkListMessage::from_chunks(
+            MessageType::ChunkInfo,
+            data.content.clone(),
+            available_chunks,
+        );
+        crate::send_or_log(udp_socket, &message.serialize(), data.address, error_stats);
+    }
+
+    let message = data.with_decremented_ttl();
+    if message.peer_ttl > 0 {
+        println!("Sending query message");
+
+        println!(
+            "{}",
+            message
+                .chunk_info
+                .chunks
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        config
+            .forwarding
+            .select(&neighbours.alive(), Some(remote_address))
+            .into_iter()
+            .for_each(|peer| {
+                crate::send_or_log(udp_socket, &message.serialize(), peer, error_stats);
+            });
+    }
+}
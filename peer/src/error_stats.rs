@@ -0,0 +1,51 @@
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use common::ProtocolError;
+
+/// Counts the errors the peer recovered from. Every error is logged along with the running
+/// totals, and the peer carries on with the next message.
+#[derive(Default)]
+pub struct ErrorStats {
+    receive_errors: AtomicU64,
+    decode_errors: AtomicU64,
+    send_errors: AtomicU64,
+}
+
+impl ErrorStats {
+    pub fn record_receive_error(&self, err: &io::Error) {
+        self.receive_errors.fetch_add(1, Ordering::Relaxed);
+        eprintln!("Failed to read from udp socket: {}. {}", err, self);
+    }
+
+    pub fn record_decode_error(&self, remote_address: &SocketAddr, err: &ProtocolError) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+        eprintln!(
+            "Dropping malformed message from {}: {}. {}",
+            remote_address, err, self
+        );
+    }
+
+    pub fn record_send_error(&self, remote_address: &SocketAddr, err: &io::Error) {
+        self.send_errors.fetch_add(1, Ordering::Relaxed);
+        eprintln!(
+            "Failed to communicate with {}: {}. {}",
+            remote_address, err, self
+        );
+    }
+}
+
+impl fmt::Display for ErrorStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Errors so far: {} receive, {} decode, {} send",
+            self.receive_errors.load(Ordering::Relaxed),
+            self.decode_errors.load(Ordering::Relaxed),
+            self.send_errors.load(Ordering::Relaxed)
+        )
+    }
+}
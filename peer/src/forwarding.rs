@@ -0,0 +1,127 @@
+use std::{net::SocketAddr, str::FromStr};
+
+use crate::random;
+
+/// Decides which neighbours a query is forwarded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardingStrategy {
+    /// Every neighbour except the one the query came from.
+    Flood,
+    /// At most `k` neighbours picked at random, excluding the one the query came from.
+    RandomNeighbours(usize),
+}
+
+impl ForwardingStrategy {
+    pub fn select(
+        &self,
+        neighbours: &[SocketAddr],
+        sender: Option<&SocketAddr>,
+    ) -> Vec<SocketAddr> {
+        let mut candidates: Vec<SocketAddr> = neighbours
+            .iter()
+            .filter(|&neighbour| Some(neighbour) != sender)
+            .copied()
+            .collect();
+
+        match *self {
+            ForwardingStrategy::Flood => candidates,
+            ForwardingStrategy::RandomNeighbours(k) => {
+                // Partial Fisher-Yates shuffle: only the first k positions are needed.
+                let k = k.min(candidates.len());
+                for i in 0..k {
+                    let j = i + (random::random_u64() as usize) % (candidates.len() - i);
+                    candidates.swap(i, j);
+                }
+                candidates.truncate(k);
+                candidates
+            }
+        }
+    }
+}
+
+impl FromStr for ForwardingStrategy {
+    type Err = String;
+
+    /// Parses `flood` or `random:<k>`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value == "flood" {
+            return Ok(ForwardingStrategy::Flood);
+        }
+
+        match value.strip_prefix("random:") {
+            Some(k) => k
+                .parse()
+                .map(ForwardingStrategy::RandomNeighbours)
+                .map_err(|_| format!("Invalid neighbour count in '{}'", value)),
+            None => Err(format!("Unknown forwarding strategy '{}'", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbours(ports: &[u16]) -> Vec<SocketAddr> {
+        ports
+            .iter()
+            .map(|&port| SocketAddr::from(([127, 0, 0, 1], port)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_strategies() {
+        assert_eq!("flood".parse(), Ok(ForwardingStrategy::Flood));
+        assert_eq!(
+            "random:3".parse(),
+            Ok(ForwardingStrategy::RandomNeighbours(3))
+        );
+        assert_eq!(
+            "random:0".parse(),
+            Ok(ForwardingStrategy::RandomNeighbours(0))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_strategies_and_bad_counts() {
+        assert_eq!(
+            "gossip".parse::<ForwardingStrategy>(),
+            Err("Unknown forwarding strategy 'gossip'".to_string())
+        );
+        for value in &["random:", "random:x", "random:-1"] {
+            assert_eq!(
+                value.parse::<ForwardingStrategy>(),
+                Err(format!("Invalid neighbour count in '{}'", value))
+            );
+        }
+    }
+
+    #[test]
+    fn flood_selects_every_neighbour_but_the_sender() {
+        let all = neighbours(&[1, 2, 3]);
+
+        assert_eq!(ForwardingStrategy::Flood.select(&all, None), all);
+        assert_eq!(
+            ForwardingStrategy::Flood.select(&all, Some(&all[1])),
+            neighbours(&[1, 3])
+        );
+    }
+
+    #[test]
+    fn random_selects_at_most_k_distinct_neighbours_but_the_sender() {
+        let all = neighbours(&[1, 2, 3, 4, 5]);
+
+        for _ in 0..50 {
+            let mut selected = ForwardingStrategy::RandomNeighbours(3).select(&all, Some(&all[0]));
+            assert_eq!(selected.len(), 3);
+            assert!(!selected.contains(&all[0]));
+            selected.sort();
+            selected.dedup();
+            assert_eq!(selected.len(), 3);
+        }
+
+        let mut selected = ForwardingStrategy::RandomNeighbours(10).select(&all, Some(&all[0]));
+        selected.sort();
+        assert_eq!(selected, neighbours(&[2, 3, 4, 5]));
+    }
+}
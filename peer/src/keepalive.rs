@@ -0,0 +1,66 @@
+use common::{KeepaliveInfo, Message};
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::error_stats::ErrorStats;
+use crate::neighbours::Neighbours;
+use crate::peer_config::PeerConfig;
+
+/// How often a dead neighbour is pinged to find out whether it came back.
+const DEAD_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a neighbour stays dead before it is forgotten.
+const DEAD_NEIGHBOUR_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Starts the thread pinging the neighbours every `config.ping_interval`, if set. Neighbours
+/// missing `config.max_missed_pongs` pongs in a row are marked dead, and forgotten after
+/// `DEAD_NEIGHBOUR_TIMEOUT`.
+pub fn spawn(
+    neighbours: Arc<Neighbours>,
+    udp_socket: Arc<UdpSocket>,
+    config: Arc<PeerConfig>,
+    error_stats: Arc<ErrorStats>,
+) -> Option<JoinHandle<()>> {
+    let interval = config.ping_interval?;
+
+    Some(thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        for (neighbour, nonce) in neighbours.next_pings(
+            config.max_missed_pongs,
+            DEAD_PROBE_INTERVAL,
+            DEAD_NEIGHBOUR_TIMEOUT,
+        ) {
+            let message = Message::Ping(KeepaliveInfo::ping(nonce));
+            crate::send_or_log(&udp_socket, &message.serialize(), neighbour, &error_stats);
+        }
+    }))
+}
+
+pub fn handle_ping(
+    udp_socket: &UdpSocket,
+    data: KeepaliveInfo,
+    remote_address: SocketAddr,
+    error_stats: &ErrorStats,
+) {
+    let message = Message::Pong(KeepaliveInfo::pong(data.nonce));
+    crate::send_or_log(
+        udp_socket,
+        &message.serialize(),
+        remote_address,
+        error_stats,
+    );
+}
+
+pub fn handle_pong(neighbours: &Neighbours, data: KeepaliveInfo, remote_address: SocketAddr) {
+    if let Some(rtt) = neighbours.record_pong(&remote_address, data.nonce) {
+        println!(
+            "Pong from {}, RTT {:.1} ms",
+            remote_address,
+            rtt.as_secs_f64() * 1000.0
+        );
+    }
+}
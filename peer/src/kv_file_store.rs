@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use crate::chunk_manager::{Chunk, ChunkKey};
+use crate::chunk_store::ChunkStore;
+use crate::manifest::{self, ParseMode};
+
+/// Chunks listed in a key-value file, one `[<content>/]<id>: <path>` line per chunk (see
+/// `manifest`).
+/// Chunk files are read on demand; new chunks are written next to the key-value file and
+/// appended to it.
+pub struct KvFileStore {
+    kv_file_path: PathBuf,
+    paths: RwLock<HashMap<ChunkKey, PathBuf>>,
+}
+
+impl KvFileStore {
+    pub fn open(kv_file_path: &Path, mode: ParseMode) -> Result<KvFileStore, String> {
+        let manifest = manifest::load(kv_file_path, mode)?;
+
+        let paths = manifest
+            .entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.path))
+            .collect();
+
+        Ok(KvFileStore {
+            kv_file_path: kv_file_path.to_path_buf(),
+            paths: RwLock::new(paths),
+        })
+    }
+}
+
+impl ChunkStore for KvFileStore {
+    fn get(&self, key: &ChunkKey) -> io::Result<Option<Arc<Chunk>>> {
+        let path = match self.paths.read().unwrap().get(key) {
+            Some(path) => path.clone(),
+            None => return Ok(None),
+        };
+
+        fs::read(path).map(|content| Some(Arc::new(content)))
+    }
+
+    fn contains(&self, key: &ChunkKey) -> bool {
+        self.paths.read().unwrap().contains_key(key)
+    }
+
+    fn list(&self) -> Vec<ChunkKey> {
+        let mut keys: Vec<ChunkKey> = self.paths.read().unwrap().keys().cloned().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    fn insert(&self, key: ChunkKey, chunk: Chunk) -> io::Result<()> {
+        let file_name = chunk_file_name(&key);
+        let directory = self.kv_file_path.parent().unwrap_or_else(|| Path::new(""));
+        let path = directory.join(&file_name);
+        fs::write(&path, chunk)?;
+
+        let mut kv_file = OpenOptions::new().append(true).open(&self.kv_file_path)?;
+        writeln!(kv_file, "{}: {}", key, file_name)?;
+
+        self.paths.write().unwrap().insert(key, path);
+        Ok(())
+    }
+}
+
+/// `<id>.m4s` for the default content and `<content>_<id>.m4s` otherwise. `%`, `/` and `_`
+/// in the content name are percent-encoded, so that no two chunks share a file name.
+fn chunk_file_name(key: &ChunkKey) -> String {
+    if key.content.is_default() {
+        return format!("{}.m4s", key.chunk_id);
+    }
+
+    let mut content = String::new();
+    for c in key.content.as_str().chars() {
+        match c {
+            '%' => content.push_str("%25"),
+            '/' => content.push_str("%2F"),
+            '_' => content.push_str("%5F"),
+            c => content.push(c),
+        }
+    }
+    format!("{}_{}.m4s", content, key.chunk_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{ChunkId, ContentId};
+
+    fn file_name(content: &str, chunk_id: ChunkId) -> String {
+        chunk_file_name(&ChunkKey::new(ContentId::new(content).unwrap(), chunk_id))
+    }
+
+    #[test]
+    fn chunk_file_names_are_unique() {
+        assert_eq!(file_name("", 5), "5.m4s");
+        assert_eq!(file_name("sintel", 5), "sintel_5.m4s");
+        assert_eq!(file_name("a/b", 5), "a%2Fb_5.m4s");
+        assert_eq!(file_name("a_b", 5), "a%5Fb_5.m4s");
+        assert_eq!(file_name("a%2Fb", 5), "a%252Fb_5.m4s");
+    }
+}
@@ -0,0 +1,433 @@
+use common::Message;
+use std::{
+    convert::TryFrom,
+    io,
+    net::{SocketAddr, UdpSocket},
+    path::Path,
+    sync::{mpsc, Arc},
+};
+
+pub mod peer_config;
+use peer_config::PeerConfig;
+
+mod announcer;
+
+mod archive_store;
+
+mod catalogue;
+use catalogue::Catalogue;
+
+mod chunk_cache;
+
+pub mod chunk_manager;
+use chunk_manager::{ChunkKey, ChunkManager};
+
+pub mod chunk_store;
+use chunk_store::ChunkStore;
+
+mod dht;
+use dht::Dht;
+
+pub mod directory_store;
+
+mod discovery;
+use discovery::DiscoveryRequest;
+
+mod error_stats;
+use error_stats::ErrorStats;
+
+mod forwarding;
+
+mod keepalive;
+
+mod kv_file_store;
+
+mod manifest;
+
+mod membership;
+
+mod memory_store;
+
+mod neighbours;
+use neighbours::Neighbours;
+
+mod provider_records;
+
+mod random;
+
+mod routing_table;
+
+mod seen_queries;
+
+mod serve_queue;
+use serve_queue::ServeQueue;
+
+mod serving;
+use serving::ServeJob;
+
+/// Serves the chunks of `chunk_store` over `udp_socket`, which must be blocking, until the
+/// process is stopped. It only returns if some background task cannot be started. Unlike the
+/// peer binary, it does not leave the swarm on SIGINT or SIGTERM; see `Peer::leave_on_signal`.
+pub fn run(
+    config: Arc<PeerConfig>,
+    chunk_store: Box<dyn ChunkStore>,
+    udp_socket: UdpSocket,
+) -> Result<(), String> {
+    Peer::start(config, chunk_store, udp_socket)?.serve()
+}
+
+/// A peer whose background tasks are running.
+///
+/// The thread calling `serve` only receives and decodes datagrams. Discovery (Hello and Query)
+/// runs in its own thread and chunks are served by a pool of workers, so no single heavy
+/// request stalls the rest of the node. Anything going wrong while handling a message is
+/// logged and counted, and the peer moves on to the next one.
+pub struct Peer {
+    config: Arc<PeerConfig>,
+    udp_socket: Arc<UdpSocket>,
+    error_stats: Arc<ErrorStats>,
+    neighbours: Arc<Neighbours>,
+    discovery_sender: mpsc::Sender<DiscoveryRequest>,
+    serve_queue: Arc<ServeQueue<ServeJob>>,
+    dht: Option<Arc<Dht>>,
+}
+
+impl Peer {
+    /// Starts the background tasks of a peer serving the chunks of `chunk_store` over
+    /// `udp_socket`, which must be blocking, and joins the swarm.
+    pub fn start(
+        config: Arc<PeerConfig>,
+        chunk_store: Box<dyn ChunkStore>,
+        udp_socket: UdpSocket,
+    ) -> Result<Peer, String> {
+        let catalogue = Arc::new(Catalogue::new(ChunkManager::new(
+            chunk_store,
+            config.cache_size,
+        )));
+        let udp_socket = Arc::new(udp_socket);
+
+        let error_stats = Arc::new(ErrorStats::default());
+        let neighbours = Arc::new(Neighbours::new(&config.known_peers, config.max_neighbours));
+
+        let (discovery_sender, discovery_receiver) = mpsc::channel();
+        catalogue::spawn_watcher(catalogue.clone(), config.clone())?;
+
+        membership::join(&udp_socket, &neighbours, &config, &error_stats);
+        membership::spawn_exchange(neighbours.clone(), udp_socket.clone(), error_stats.clone());
+        keepalive::spawn(
+            neighbours.clone(),
+            udp_socket.clone(),
+            config.clone(),
+            error_stats.clone(),
+        );
+
+        announcer::spawn(
+            catalogue.clone(),
+            udp_socket.clone(),
+            config.clone(),
+            error_stats.clone(),
+        );
+
+        let dht = if config.dht {
+            let own_id = config.node_id.unwrap_or_else(random::random_u64);
+            let dht = Arc::new(Dht::new(
+                own_id,
+                udp_socket.clone(),
+                &config,
+                error_stats.clone(),
+            ));
+            dht::spawn(
+                dht.clone(),
+                catalogue.clone(),
+                neighbours.clone(),
+                config.clone(),
+            );
+            Some(dht)
+        } else {
+            None
+        };
+
+        discovery::spawn(
+            discovery_receiver,
+            catalogue.clone(),
+            neighbours.clone(),
+            udp_socket.clone(),
+            config.clone(),
+            error_stats.clone(),
+        );
+
+        let serve_queue = Arc::new(ServeQueue::new(serve_queue::MAX_JOBS_PER_CLIENT));
+        serving::spawn_workers(
+            config.workers,
+            serve_queue.clone(),
+            catalogue,
+            udp_socket.clone(),
+            error_stats.clone(),
+        );
+
+        Ok(Peer {
+            config,
+            udp_socket,
+            error_stats,
+            neighbours,
+            discovery_sender,
+            serve_queue,
+            dht,
+        })
+    }
+
+    /// Starts the thread that, on SIGINT or SIGTERM, says Bye to the neighbours and the
+    /// tracker and exits the process. It is up to the program, as it ends the whole process.
+    pub fn leave_on_signal(&self) -> Result<(), String> {
+        membership::spawn_leave_handler(
+            self.neighbours.clone(),
+            self.udp_socket.clone(),
+            self.config.clone(),
+            self.error_stats.clone(),
+        )?;
+        Ok(())
+    }
+
+    /// Receives and handles datagrams until the process is stopped.
+    pub fn serve(&self) -> ! {
+        loop {
+            let mut buffer = [0; 60 * 1024];
+            // Errors here are usually ICMP port unreachable reports for datagrams sent earlier to
+            // a departed host, and say nothing about the next datagram.
+            let (bytes_read, remote_address) = match self.udp_socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(err) => {
+                    self.error_stats.record_receive_error(&err);
+                    continue;
+                }
+            };
+            let remote_address = common::canonical_address(remote_address);
+
+            println!("Read {} bytes from {}", bytes_read, remote_address);
+
+            let message = match Message::new(&buffer, bytes_read) {
+                Ok(message) => message,
+                Err(err) => {
+                    self.error_stats.record_decode_error(&remote_address, &err);
+                    continue;
+                }
+            };
+            match message {
+                Message::Hello(data) => {
+                    dispatch_discovery(
+                        &self.discovery_sender,
+                        DiscoveryRequest::Hello(data, remote_address),
+                    );
+                }
+                Message::Query(data) => {
+                    dispatch_discovery(
+                        &self.discovery_sender,
+                        DiscoveryRequest::Query(data, remote_address),
+                    );
+                }
+                Message::Get(data) => {
+                    println!(
+                        "Got GET message! Client is asking for {} chunks of content {}",
+                        data.chunk_list.chunks.len(),
+                        data.content
+                    );
+                    let mut dropped = 0;
+                    for chunk_id in data.chunk_list.chunks {
+                        let key = ChunkKey::new(data.content.clone(), chunk_id);
+                        if !self.serve_queue.push(remote_address, ServeJob::Chunk(key)) {
+                            dropped += 1;
+                        }
+                    }
+                    if dropped > 0 {
+                        println!(
+                            "Dropped {} requests from {}, which has too many waiting",
+                            dropped, remote_address
+                        );
+                    }
+                }
+                Message::GetFragments(data) => {
+                    println!(
+                        "Got GET FRAGMENTS message! Client is asking for {} fragments of chunk {}",
+                        data.fragments.chunks.len(),
+                        data.chunk_id
+                    );
+                    // Chunks never have more than u16::MAX + 1 fragments, so longer lists are
+                    // refused before being copied.
+                    if data.fragments.chunks.len() > usize::from(u16::MAX) + 1 {
+                        println!(
+                            "Ignoring request from {} for {} fragments",
+                            remote_address,
+                            data.fragments.chunks.len()
+                        );
+                        continue;
+                    }
+                    let fragments = data
+                        .fragments
+                        .chunks
+                        .into_iter()
+                        .filter_map(|index| u16::try_from(index).ok())
+                        .collect();
+                    let key = ChunkKey::new(data.content, data.chunk_id);
+                    if !self
+                        .serve_queue
+                        .push(remote_address, ServeJob::Fragments(key, fragments))
+                    {
+                        println!(
+                            "Dropped request from {}, which has too many waiting",
+                            remote_address
+                        );
+                    }
+                }
+                Message::Join => {
+                    membership::handle_join(
+                        &self.udp_socket,
+                        &self.neighbours,
+                        remote_address,
+                        &self.error_stats,
+                    );
+                }
+                Message::Bye => membership::handle_bye(&self.neighbours, remote_address),
+                Message::PeerExchange(data) => {
+                    membership::handle_peer_exchange(
+                        &self.udp_socket,
+                        &self.neighbours,
+                        data,
+                        remote_address,
+                        &self.config,
+                        &self.error_stats,
+                    );
+                }
+                Message::Ping(data) => {
+                    keepalive::handle_ping(
+                        &self.udp_socket,
+                        data,
+                        remote_address,
+                        &self.error_stats,
+                    );
+                }
+                Message::Pong(data) => {
+                    keepalive::handle_pong(&self.neighbours, data, remote_address)
+                }
+                Message::FindNode(data) | Message::FindProviders(data) => {
+                    if let Some(dht) = &self.dht {
+                        dht.handle_lookup(data, remote_address);
+                    }
+                }
+                Message::Nodes(data) => {
+                    if let Some(dht) = &self.dht {
+                        dht.handle_nodes(data, remote_address);
+                    }
+                }
+                Message::AddProvider(data) => {
+                    if let Some(dht) = &self.dht {
+                        dht.handle_add_provider(data, remote_address);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn dispatch_discovery(sender: &mpsc::Sender<DiscoveryRequest>, request: DiscoveryRequest) {
+    if sender.send(request).is_err() {
+        eprintln!("Discovery task stopped, dropping message");
+    }
+}
+
+/// Sends `data` to `address`, logging the outcome. Failures are counted in `error_stats`.
+pub(crate) fn send_or_log(
+    udp_socket: &UdpSocket,
+    data: &[u8],
+    address: SocketAddr,
+    error_stats: &ErrorStats,
+) {
+    match send_to(udp_socket, data, address) {
+        Ok(amt) => println!("Sent {} bytes to {}", amt, address),
+        Err(err) => error_stats.record_send_error(&address, &err),
+    }
+}
+
+/// Sends `data` to `address`, mapping IPv4 destinations when the peer is bound to an IPv6
+/// (dual-stack) address.
+pub(crate) fn send_to(
+    udp_socket: &UdpSocket,
+    data: &[u8],
+    address: SocketAddr,
+) -> io::Result<usize> {
+    let local_address = udp_socket.local_addr()?;
+    udp_socket.send_to(data, common::reachable_address(&local_address, address))
+}
+
+/// Opens the configured chunk store, first copying into it the chunks to import, if any.
+pub fn open_store(config: &PeerConfig) -> Result<Box<dyn ChunkStore>, String> {
+    let store = config
+        .store
+        .open(Path::new(&config.store_path), config.manifest_mode)?;
+
+    if let Some(import_path) = &config.import_path {
+        chunk_store::import_chunks(store.as_ref(), Path::new(import_path), config.manifest_mode)?;
+    }
+
+    println!("Hosting {} chunks", store.list().len());
+    Ok(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{ChunkListMessage, ContentId, MessageType};
+    use std::{fs, thread, time::Duration};
+
+    use crate::chunk_store::StoreKind;
+    use crate::directory_store::DirectoryStore;
+
+    #[test]
+    fn serves_a_get_from_a_seeded_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let chunk: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+        fs::write(directory.path().join("5.m4s"), &chunk).unwrap();
+        let store = DirectoryStore::open(directory.path()).unwrap();
+
+        let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut config = PeerConfig::with_defaults(
+            udp_socket.local_addr().unwrap(),
+            directory.path().display().to_string(),
+        );
+        config.store = StoreKind::Directory;
+        config.reload_interval = None;
+        config.ping_interval = None;
+        let peer_address = config.address;
+        thread::spawn(move || run(Arc::new(config), Box::new(store), udp_socket));
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let get = Message::Get(ChunkListMessage::from_chunks(
+            MessageType::Get,
+            ContentId::default(),
+            vec![5],
+        ));
+        client.send_to(&get.serialize(), peer_address).unwrap();
+
+        let mut fragments = Vec::new();
+        loop {
+            let mut buffer = [0; 60 * 1024];
+            let (bytes_read, _address) = client.recv_from(&mut buffer).unwrap();
+            let data = match Message::new(&buffer, bytes_read).unwrap() {
+                Message::Response(data) => data,
+                _ => continue,
+            };
+            assert_eq!(data.chunk_id, 5);
+            fragments.push((data.fragment_index, data.fragment));
+            if fragments.len() == usize::from(data.fragment_count) {
+                break;
+            }
+        }
+
+        fragments.sort();
+        let received: Vec<u8> = fragments.into_iter().flat_map(|(_i, f)| f).collect();
+        assert_eq!(received, chunk);
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use common::ContentId;
+
+use crate::chunk_manager::{ChunkId, ChunkKey};
+
+/// How to deal with manifest lines that cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// The first bad line fails the whole manifest.
+    Strict,
+    /// Bad lines are reported and left out.
+    SkipBadEntries,
+}
+
+/// A chunk listed in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub key: ChunkKey,
+    /// Path of the chunk file, already resolved against the manifest's directory.
+    pub path: PathBuf,
+}
+
+/// A problem found in a manifest line. Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+    /// Lines left out in `ParseMode::SkipBadEntries`.
+    pub skipped: Vec<ManifestError>,
+}
+
+/// Reads the manifest at `path` and checks that every chunk file it lists exists.
+pub fn load(path: &Path, mode: ParseMode) -> Result<Manifest, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to open manifest {}: {}", path.display(), err))?;
+    let base_directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let manifest = parse(&contents, base_directory, mode, |entry_path| {
+        fs::metadata(entry_path).map(|_metadata| ()).map_err(|err| {
+            format!(
+                "unable to read chunk file {}: {}",
+                entry_path.display(),
+                err
+            )
+        })
+    })
+    .map_err(|err| format!("{}:{}", path.display(), err))?;
+
+    for skipped in &manifest.skipped {
+        eprintln!("{}:{} (entry skipped)", path.display(), skipped);
+    }
+
+    Ok(manifest)
+}
+
+/// Parses manifest `contents`, made of `[<content>/]<id>: <path>` lines, where chunks
+/// without a content name belong to the default content. Blank lines and lines starting
+/// with `#` are ignored, whitespace around the ID and path is trimmed, and both LF and CRLF
+/// line endings are accepted. Relative paths are resolved against `base_directory`, and
+/// every path is passed to `check_path` before being accepted.
+pub fn parse<F>(
+    contents: &str,
+    base_directory: &Path,
+    mode: ParseMode,
+    check_path: F,
+) -> Result<Manifest, ManifestError>
+where
+    F: Fn(&Path) -> Result<(), String>,
+{
+    let mut manifest = Manifest::default();
+    let mut first_lines: HashMap<ChunkKey, usize> = HashMap::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+
+        let entry = match parse_line(line, line_number, base_directory) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => continue,
+            Err(err) => {
+                skip_or_fail(&mut manifest, mode, err)?;
+                continue;
+            }
+        };
+
+        if let Some(first_line) = first_lines.get(&entry.key) {
+            let err = ManifestError {
+                line: line_number,
+                column: first_column(line),
+                message: format!(
+                    "chunk {} is already listed on line {}",
+                    entry.key, first_line
+                ),
+            };
+            skip_or_fail(&mut manifest, mode, err)?;
+            continue;
+        }
+
+        if let Err(message) = check_path(&entry.path) {
+            let err = ManifestError {
+                line: line_number,
+                column: path_column(line),
+                message,
+            };
+            skip_or_fail(&mut manifest, mode, err)?;
+            continue;
+        }
+
+        first_lines.insert(entry.key.clone(), line_number);
+        manifest.entries.push(entry);
+    }
+
+    Ok(manifest)
+}
+
+fn skip_or_fail(
+    manifest: &mut Manifest,
+    mode: ParseMode,
+    err: ManifestError,
+) -> Result<(), ManifestError> {
+    match mode {
+        ParseMode::Strict => Err(err),
+        ParseMode::SkipBadEntries => {
+            manifest.skipped.push(err);
+            Ok(())
+        }
+    }
+}
+
+/// Parses a single line, returning `None` for blank lines and comments.
+fn parse_line(
+    line: &str,
+    line_number: usize,
+    base_directory: &Path,
+) -> Result<Option<ManifestEntry>, ManifestError> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+
+    let error = |column: usize, message: String| ManifestError {
+        line: line_number,
+        column,
+        message,
+    };
+
+    let colon = line.find(':').ok_or_else(|| {
+        error(
+            line.trim_end().chars().count() + 1,
+            "expected ':' between chunk ID and path".to_string(),
+        )
+    })?;
+
+    let key = line[..colon].trim();
+    if key.is_empty() {
+        return Err(error(first_column(line), "missing chunk ID".to_string()));
+    }
+
+    let (content, chunk_id) = match key.rfind('/') {
+        Some(slash) => (&key[..slash], &key[slash + 1..]),
+        None => ("", key),
+    };
+    let key_start = line.len() - line.trim_start().len();
+    let chunk_id_column = column_of(line, key_start + key.len() - chunk_id.len());
+
+    let content =
+        ContentId::new(content).map_err(|err| error(first_column(line), err.to_string()))?;
+    if content.is_default() && key.contains('/') {
+        return Err(error(
+            first_column(line),
+            "missing content name".to_string(),
+        ));
+    }
+    let chunk_id: ChunkId = chunk_id.parse().map_err(|_| {
+        error(
+            chunk_id_column,
+            format!(
+                "chunk ID '{}' is not a number between 0 and {}",
+                chunk_id,
+                ChunkId::MAX
+            ),
+        )
+    })?;
+
+    let path = line[colon + 1..].trim();
+    if path.is_empty() {
+        return Err(error(
+            column_of(line, colon) + 1,
+            "missing path".to_string(),
+        ));
+    }
+
+    Ok(Some(ManifestEntry {
+        key: ChunkKey::new(content, chunk_id),
+        path: base_directory.join(path),
+    }))
+}
+
+/// Column of the first non-whitespace character of `line`.
+fn first_column(line: &str) -> usize {
+    column_of(line, line.len() - line.trim_start().len())
+}
+
+/// Column of the first non-whitespace character after the colon.
+fn path_column(line: &str) -> usize {
+    let colon = line.find(':').unwrap_or(0);
+    let after_colon = &line[colon + 1..];
+    column_of(
+        line,
+        colon + 1 + after_colon.len() - after_colon.trim_start().len(),
+    )
+}
+
+/// 1-based column of the character starting at `byte_index`.
+fn column_of(line: &str, byte_index: usize) -> usize {
+    line[..byte_index].chars().count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(contents: &str, mode: ParseMode) -> Result<Manifest, ManifestError> {
+        parse(contents, Path::new("/store"), mode, |_path| Ok(()))
+    }
+
+    fn error_at(contents: &str) -> (usize, usize, String) {
+        let err = parse_all(contents, ParseMode::Strict).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    fn key(content: &str, chunk_id: ChunkId) -> ChunkKey {
+        ChunkKey::new(ContentId::new(content).unwrap(), chunk_id)
+    }
+
+    #[test]
+    fn parses_entries_skipping_comments_and_blank_lines() {
+        let manifest = parse_all(
+            "# chunks\n\n5: a.m4s\n   \n  # indented comment\nsintel/720p/6: b.m4s\n",
+            ParseMode::Strict,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.entries,
+            vec![
+                ManifestEntry {
+                    key: key("", 5),
+                    path: PathBuf::from("/store/a.m4s"),
+                },
+                ManifestEntry {
+                    key: key("sintel/720p", 6),
+                    path: PathBuf::from("/store/b.m4s"),
+                },
+            ]
+        );
+        assert!(manifest.skipped.is_empty());
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings_and_surrounding_whitespace() {
+        let manifest = parse_all("  5 :  a.m4s  \r\n6:b.m4s\r\n", ParseMode::Strict).unwrap();
+
+        assert_eq!(manifest.entries[0].key, key("", 5));
+        assert_eq!(manifest.entries[0].path, PathBuf::from("/store/a.m4s"));
+        assert_eq!(manifest.entries[1].path, PathBuf::from("/store/b.m4s"));
+    }
+
+    #[test]
+    fn resolves_relative_paths_against_the_base_directory() {
+        let manifest =
+            parse_all("1: ../chunks/a.m4s\n2: /data/b.m4s\n", ParseMode::Strict).unwrap();
+
+        assert_eq!(
+            manifest.entries[0].path,
+            PathBuf::from("/store/../chunks/a.m4s")
+        );
+        assert_eq!(manifest.entries[1].path, PathBuf::from("/data/b.m4s"));
+    }
+
+    #[test]
+    fn reports_line_and_column_of_each_error() {
+        assert_eq!(
+            error_at("1: a\n  5 a.m4s  "),
+            (2, 10, "expected ':' between chunk ID and path".to_string())
+        );
+        assert_eq!(
+            error_at("  : a.m4s"),
+            (1, 3, "missing chunk ID".to_string())
+        );
+        assert_eq!(
+            error_at(" sintel/x5: a.m4s"),
+            (
+                1,
+                9,
+                format!(
+                    "chunk ID 'x5' is not a number between 0 and {}",
+                    ChunkId::MAX
+                )
+            )
+        );
+        assert_eq!(error_at("5:   "), (1, 3, "missing path".to_string()));
+        assert_eq!(
+            error_at("\n /5: a.m4s"),
+            (2, 2, "missing content name".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_chunk_ids() {
+        assert_eq!(
+            error_at("5: a.m4s\nsintel/5: b.m4s\n 5: c.m4s"),
+            (3, 2, "chunk 5 is already listed on line 1".to_string())
+        );
+        assert_eq!(
+            error_at("sintel/5: a.m4s\nsintel/5: b.m4s").2,
+            "chunk sintel/5 is already listed on line 1"
+        );
+    }
+
+    #[test]
+    fn reports_paths_rejected_by_the_check() {
+        let err = parse(
+            "5:  missing.m4s",
+            Path::new(""),
+            ParseMode::Strict,
+            |path| Err(format!("no {}", path.display())),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            ManifestError {
+                line: 1,
+                column: 5,
+                message: "no missing.m4s".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn skip_mode_keeps_the_good_entries() {
+        let contents = "1: a.m4s\nbad line\n1: again.m4s\n2: b.m4s\n";
+
+        let manifest = parse_all(contents, ParseMode::SkipBadEntries).unwrap();
+
+        let keys: Vec<ChunkKey> = manifest
+            .entries
+            .iter()
+            .map(|entry| entry.key.clone())
+            .collect();
+        assert_eq!(keys, vec![key("", 1), key("", 2)]);
+        let skipped: Vec<usize> = manifest.skipped.iter().map(|err| err.line).collect();
+        assert_eq!(skipped, vec![2, 3]);
+
+        assert_eq!(parse_all(contents, ParseMode::Strict).unwrap_err().line, 2);
+    }
+}
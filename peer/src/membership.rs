@@ -0,0 +1,174 @@
+use common::{Message, PeerExchangeInfo};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use std::{
+    net::{SocketAddr, UdpSocket},
+    process,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::error_stats::ErrorStats;
+use crate::neighbours::Neighbours;
+use crate::peer_config::PeerConfig;
+
+/// Amount of neighbours shared in a PeerExchange.
+const EXCHANGE_SAMPLE_SIZE: usize = 8;
+/// How often a sample of the neighbours is sent to one of them.
+const EXCHANGE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Asks the bootstrap peers to take this node as a neighbour. They answer with a sample of
+/// their own neighbours, which are then joined as well while there is room.
+pub fn join(
+    udp_socket: &UdpSocket,
+    neighbours: &Neighbours,
+    config: &PeerConfig,
+    error_stats: &ErrorStats,
+) {
+    for bootstrap_peer in &config.bootstrap_peers {
+        println!("Joining the swarm through {}", bootstrap_peer);
+        neighbours.add(*bootstrap_peer);
+        crate::send_or_log(
+            udp_socket,
+            &Message::Join.serialize(),
+            *bootstrap_peer,
+            error_stats,
+        );
+    }
+}
+
+/// Takes the sender as a neighbour if there is room, and answers with a sample of the
+/// neighbours either way, so that a peer turned away still learns where else to join.
+pub fn handle_join(
+    udp_socket: &UdpSocket,
+    neighbours: &Neighbours,
+    remote_address: SocketAddr,
+    error_stats: &ErrorStats,
+) {
+    if neighbours.admit(remote_address) {
+        println!("{} joined", remote_address);
+    } else {
+        println!(
+            "Turning {} away, there are enough neighbours",
+            remote_address
+        );
+    }
+
+    let sample = neighbours.sample(EXCHANGE_SAMPLE_SIZE, Some(&remote_address));
+    let message = Message::PeerExchange(PeerExchangeInfo::from_peers(sample));
+    crate::send_or_log(
+        udp_socket,
+        &message.serialize(),
+        remote_address,
+        error_stats,
+    );
+}
+
+/// Forgets a neighbour that announced it is leaving. A Bye from anyone else is ignored, as
+/// peer exchanges are.
+pub fn handle_bye(neighbours: &Neighbours, remote_address: SocketAddr) {
+    if !neighbours.contains(&remote_address) {
+        println!(
+            "Ignoring Bye from {}, which is not a neighbour",
+            remote_address
+        );
+        return;
+    }
+
+    neighbours.remove(&remote_address);
+    println!("{} left", remote_address);
+}
+
+/// Takes the peers shared by a neighbour or a bootstrap peer as neighbours while there is
+/// room, sending a Join to every new one so that the link goes both ways. Exchanges from
+/// anyone else are ignored, so that strangers can neither fill the table nor have this peer
+/// send Joins on their behalf.
+pub fn handle_peer_exchange(
+    udp_socket: &UdpSocket,
+    neighbours: &Neighbours,
+    data: PeerExchangeInfo,
+    remote_address: SocketAddr,
+    config: &PeerConfig,
+    error_stats: &ErrorStats,
+) {
+    println!(
+        "Got PEER EXCHANGE message! {} shared {} peers",
+        remote_address,
+        data.peers.len()
+    );
+
+    if !neighbours.contains(&remote_address) && !config.bootstrap_peers.contains(&remote_address) {
+        println!(
+            "Ignoring peer exchange from {}, which is not a neighbour",
+            remote_address
+        );
+        return;
+    }
+
+    for peer in data.peers {
+        if is_own_address(config, &peer) || !neighbours.add_if_room(peer) {
+            continue;
+        }
+
+        println!("Joining {}", peer);
+        crate::send_or_log(udp_socket, &Message::Join.serialize(), peer, error_stats);
+    }
+}
+
+/// Starts the thread sending, every `EXCHANGE_INTERVAL`, a sample of the neighbours to one
+/// of them picked at random.
+pub fn spawn_exchange(
+    neighbours: Arc<Neighbours>,
+    udp_socket: Arc<UdpSocket>,
+    error_stats: Arc<ErrorStats>,
+) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        thread::sleep(EXCHANGE_INTERVAL);
+
+        for neighbour in neighbours.sample(1, None) {
+            let sample = neighbours.sample(EXCHANGE_SAMPLE_SIZE, Some(&neighbour));
+            let message = Message::PeerExchange(PeerExchangeInfo::from_peers(sample));
+            crate::send_or_log(&udp_socket, &message.serialize(), neighbour, &error_stats);
+        }
+    })
+}
+
+/// Starts the thread that, on SIGINT or SIGTERM, says Bye to every neighbour and to the
+/// tracker, if any, and exits.
+pub fn spawn_leave_handler(
+    neighbours: Arc<Neighbours>,
+    udp_socket: Arc<UdpSocket>,
+    config: Arc<PeerConfig>,
+    error_stats: Arc<ErrorStats>,
+) -> Result<JoinHandle<()>, String> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])
+        .map_err(|err| format!("Unable to listen for SIGINT and SIGTERM: {}", err))?;
+
+    Ok(thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            for neighbour in neighbours.list().into_iter().chain(config.tracker) {
+                crate::send_or_log(
+                    &udp_socket,
+                    &Message::Bye.serialize(),
+                    neighbour,
+                    &error_stats,
+                );
+            }
+
+            println!("Left the swarm");
+            process::exit(0);
+        }
+    }))
+}
+
+/// Whether `address` is how other peers see this node. A peer bound to an unspecified
+/// address is reached by its neighbours through a loopback address.
+fn is_own_address(config: &PeerConfig, address: &SocketAddr) -> bool {
+    *address == config.address
+        || (config.address.ip().is_unspecified()
+            && address.ip().is_loopback()
+            && address.port() == config.address.port())
+}
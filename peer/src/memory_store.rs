@@ -0,0 +1,35 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, RwLock},
+};
+
+use crate::chunk_manager::{Chunk, ChunkKey};
+use crate::chunk_store::ChunkStore;
+
+/// Chunks held only in memory. Nothing is persisted.
+#[derive(Default)]
+pub struct MemoryStore {
+    chunks: RwLock<HashMap<ChunkKey, Arc<Chunk>>>,
+}
+
+impl ChunkStore for MemoryStore {
+    fn get(&self, key: &ChunkKey) -> io::Result<Option<Arc<Chunk>>> {
+        Ok(self.chunks.read().unwrap().get(key).cloned())
+    }
+
+    fn contains(&self, key: &ChunkKey) -> bool {
+        self.chunks.read().unwrap().contains_key(key)
+    }
+
+    fn list(&self) -> Vec<ChunkKey> {
+        let mut keys: Vec<ChunkKey> = self.chunks.read().unwrap().keys().cloned().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    fn insert(&self, key: ChunkKey, chunk: Chunk) -> io::Result<()> {
+        self.chunks.write().unwrap().insert(key, Arc::new(chunk));
+        Ok(())
+    }
+}
@@ -0,0 +1,463 @@
+use std::{
+    net::SocketAddr,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::forwarding::ForwardingStrategy;
+use crate::random;
+
+/// The peers this node exchanges queries with. It starts from the peers given on the command
+/// line and changes as nodes join and leave the swarm. Neighbours that stop answering pings
+/// are marked dead and left out of forwarding until they answer again, and forgotten if they
+/// stay dead for too long.
+pub struct Neighbours {
+    neighbours: RwLock<Vec<Neighbour>>,
+    /// New neighbours, whether they asked to join or were learned through peer exchange, are
+    /// only taken while fewer than this many are alive. The table holds twice as many at most,
+    /// dead ones included, and once it is full the one dead the longest makes room.
+    capacity: usize,
+}
+
+struct Neighbour {
+    address: SocketAddr,
+    /// Nonce and send time of the last ping, until its pong arrives.
+    pending_ping: Option<(u32, Instant)>,
+    last_ping: Option<Instant>,
+    missed_pongs: u32,
+    /// Smoothed round-trip time, as in TCP.
+    rtt: Option<Duration>,
+    /// When the neighbour was marked dead, if it is.
+    dead_since: Option<Instant>,
+}
+
+impl Neighbour {
+    fn new(address: SocketAddr) -> Neighbour {
+        Neighbour {
+            address,
+            pending_ping: None,
+            last_ping: None,
+            missed_pongs: 0,
+            rtt: None,
+            dead_since: None,
+        }
+    }
+
+    fn revive(&mut self) {
+        self.missed_pongs = 0;
+        if self.dead_since.take().is_some() {
+            println!("Neighbour {} is back", self.address);
+        }
+    }
+}
+
+impl Neighbours {
+    pub fn new(initial: &[SocketAddr], capacity: usize) -> Neighbours {
+        let mut neighbours: Vec<Neighbour> = Vec::new();
+        for address in initial {
+            if !neighbours
+                .iter()
+                .any(|neighbour| neighbour.address == *address)
+            {
+                neighbours.push(Neighbour::new(*address));
+            }
+        }
+
+        Neighbours {
+            neighbours: RwLock::new(neighbours),
+            capacity,
+        }
+    }
+
+    /// Every neighbour, dead or alive.
+    pub fn list(&self) -> Vec<SocketAddr> {
+        self.neighbours
+            .read()
+            .unwrap()
+            .iter()
+            .map(|neighbour| neighbour.address)
+            .collect()
+    }
+
+    /// The neighbours queries are forwarded to.
+    pub fn alive(&self) -> Vec<SocketAddr> {
+        self.neighbours
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|neighbour| neighbour.dead_since.is_none())
+            .map(|neighbour| neighbour.address)
+            .collect()
+    }
+
+    pub fn contains(&self, address: &SocketAddr) -> bool {
+        self.neighbours
+            .read()
+            .unwrap()
+            .iter()
+            .any(|neighbour| neighbour.address == *address)
+    }
+
+    /// Adds `address` regardless of the capacity, returning whether it was not a neighbour
+    /// yet. A dead neighbour adding itself again is alive.
+    pub fn add(&self, address: SocketAddr) -> bool {
+        let mut neighbours = self.neighbours.write().unwrap();
+        if let Some(neighbour) = neighbours.iter_mut().find(|n| n.address == address) {
+            neighbour.revive();
+            return false;
+        }
+
+        neighbours.push(Neighbour::new(address));
+        true
+    }
+
+    /// Takes `address`, which asked to join, as a neighbour, returning whether it is one now.
+    /// A known neighbour is revived, and a new one is only added if the table is not full.
+    pub fn admit(&self, address: SocketAddr) -> bool {
+        let mut neighbours = self.neighbours.write().unwrap();
+        if let Some(neighbour) = neighbours.iter_mut().find(|n| n.address == address) {
+            neighbour.revive();
+            return true;
+        }
+
+        if !self.make_room(&mut neighbours) {
+            return false;
+        }
+
+        neighbours.push(Neighbour::new(address));
+        true
+    }
+
+    /// Adds `address` if the table is not full, returning whether it was added.
+    pub fn add_if_room(&self, address: SocketAddr) -> bool {
+        let mut neighbours = self.neighbours.write().unwrap();
+        if neighbours.iter().any(|n| n.address == address) || !self.make_room(&mut neighbours) {
+            return false;
+        }
+
+        neighbours.push(Neighbour::new(address));
+        true
+    }
+
+    /// Whether a new neighbour can be added, forgetting the neighbour dead the longest if the
+    /// table is full of dead ones.
+    fn make_room(&self, neighbours: &mut Vec<Neighbour>) -> bool {
+        let alive = neighbours
+            .iter()
+            .filter(|neighbour| neighbour.dead_since.is_none())
+            .count();
+        if alive >= self.capacity {
+            return false;
+        }
+        if neighbours.len() < self.capacity.saturating_mul(2) {
+            return true;
+        }
+
+        let longest_dead = neighbours
+            .iter()
+            .enumerate()
+            .filter_map(|(index, neighbour)| neighbour.dead_since.map(|since| (since, index)))
+            .min();
+        match longest_dead {
+            Some((_since, index)) => {
+                let forgotten = neighbours.swap_remove(index);
+                println!("Forgetting dead neighbour {}", forgotten.address);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes `address`, returning whether it was a neighbour.
+    pub fn remove(&self, address: &SocketAddr) -> bool {
+        let mut neighbours = self.neighbours.write().unwrap();
+        let before = neighbours.len();
+        neighbours.retain(|neighbour| neighbour.address != *address);
+
+        neighbours.len() != before
+    }
+
+    /// At most `size` alive neighbours picked at random, leaving out `excluded`.
+    pub fn sample(&self, size: usize, excluded: Option<&SocketAddr>) -> Vec<SocketAddr> {
+        ForwardingStrategy::RandomNeighbours(size).select(&self.alive(), excluded)
+    }
+
+    /// Counts the pings left unanswered since the previous call, marks as dead the neighbours
+    /// that missed `max_missed_pongs` in a row, and returns the neighbours to ping now along
+    /// with the nonce to use. Dead neighbours are only pinged every `dead_probe_interval`, and
+    /// forgotten once dead for `dead_timeout`.
+    pub fn next_pings(
+        &self,
+        max_missed_pongs: u32,
+        dead_probe_interval: Duration,
+        dead_timeout: Duration,
+    ) -> Vec<(SocketAddr, u32)> {
+        let now = Instant::now();
+        let mut neighbours = self.neighbours.write().unwrap();
+        neighbours.retain(|neighbour| match neighbour.dead_since {
+            Some(since) if now - since >= dead_timeout => {
+                println!(
+                    "Forgetting neighbour {}, dead for too long",
+                    neighbour.address
+                );
+                false
+            }
+            _ => true,
+        });
+
+        let mut pings = Vec::new();
+        for neighbour in neighbours.iter_mut() {
+            if neighbour.pending_ping.take().is_some() {
+                neighbour.missed_pongs = neighbour.missed_pongs.saturating_add(1);
+            }
+
+            if neighbour.dead_since.is_none() && neighbour.missed_pongs >= max_missed_pongs {
+                neighbour.dead_since = Some(now);
+                println!(
+                    "Neighbour {} missed {} pongs, marking it dead",
+                    neighbour.address, neighbour.missed_pongs
+                );
+            }
+
+            let probe_due = neighbour
+                .last_ping
+                .is_none_or(|last_ping| now - last_ping >= dead_probe_interval);
+            if neighbour.dead_since.is_some() && !probe_due {
+                continue;
+            }
+
+            let nonce = random::random_u64() as u32;
+            neighbour.pending_ping = Some((nonce, now));
+            neighbour.last_ping = Some(now);
+            pings.push((neighbour.address, nonce));
+        }
+
+        pings
+    }
+
+    /// Records the pong answering the ping with `nonce`, returning the smoothed round-trip
+    /// time. Pongs that do not match the last ping sent to `address` are ignored.
+    pub fn record_pong(&self, address: &SocketAddr, nonce: u32) -> Option<Duration> {
+        let mut neighbours = self.neighbours.write().unwrap();
+        let neighbour = neighbours.iter_mut().find(|n| n.address == *address)?;
+
+        let sent = match neighbour.pending_ping {
+            Some((pending_nonce, sent)) if pending_nonce == nonce => sent,
+            _ => return None,
+        };
+        neighbour.pending_ping = None;
+        neighbour.revive();
+
+        let sample = sent.elapsed();
+        let rtt = match neighbour.rtt {
+            Some(rtt) => (rtt * 7 + sample) / 8,
+            None => sample,
+        };
+        neighbour.rtt = Some(rtt);
+
+        Some(rtt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn sorted(mut addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        addresses.sort();
+        addresses
+    }
+
+    #[test]
+    fn initial_neighbours_are_deduplicated() {
+        let neighbours = Neighbours::new(&[peer(1), peer(2), peer(1)], 8);
+
+        assert_eq!(neighbours.list(), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn add_ignores_known_neighbours_and_the_capacity() {
+        let neighbours = Neighbours::new(&[peer(1)], 1);
+
+        assert!(!neighbours.add(peer(1)));
+        assert!(neighbours.add(peer(2)));
+        assert_eq!(neighbours.list(), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn add_if_room_respects_the_capacity() {
+        let neighbours = Neighbours::new(&[peer(1)], 2);
+
+        assert!(!neighbours.add_if_room(peer(1)));
+        assert!(neighbours.add_if_room(peer(2)));
+        assert!(!neighbours.add_if_room(peer(3)));
+        assert_eq!(neighbours.list(), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn admit_turns_strangers_away_when_full() {
+        let neighbours = Neighbours::new(&[peer(1)], 2);
+
+        assert!(neighbours.admit(peer(2)));
+        assert!(!neighbours.admit(peer(3)));
+        assert!(neighbours.admit(peer(1)));
+        assert_eq!(neighbours.list(), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn remove_reports_whether_the_peer_was_a_neighbour() {
+        let neighbours = Neighbours::new(&[peer(1), peer(2)], 8);
+
+        assert!(neighbours.remove(&peer(1)));
+        assert!(!neighbours.remove(&peer(1)));
+        assert!(!neighbours.contains(&peer(1)));
+        assert_eq!(neighbours.list(), vec![peer(2)]);
+    }
+
+    #[test]
+    fn sample_leaves_out_the_excluded_peer() {
+        let neighbours = Neighbours::new(&[peer(1), peer(2), peer(3)], 8);
+
+        assert_eq!(
+            sorted(neighbours.sample(8, Some(&peer(2)))),
+            vec![peer(1), peer(3)]
+        );
+        assert_eq!(neighbours.sample(1, None).len(), 1);
+    }
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    /// Runs keepalive rounds in which nobody answers, until `address` is marked dead.
+    fn kill(neighbours: &Neighbours, address: SocketAddr, max_missed_pongs: u32) {
+        for _ in 0..=max_missed_pongs {
+            assert!(neighbours.alive().contains(&address));
+            neighbours.next_pings(max_missed_pongs, HOUR, HOUR);
+        }
+        assert!(!neighbours.alive().contains(&address));
+    }
+
+    #[test]
+    fn neighbour_is_dead_after_missing_max_missed_pongs() {
+        let neighbours = Neighbours::new(&[peer(1), peer(2)], 8);
+
+        let mut pings = neighbours.next_pings(2, HOUR, HOUR);
+        for _ in 0..2 {
+            let nonce = pings
+                .iter()
+                .find(|(address, _)| *address == peer(2))
+                .unwrap()
+                .1;
+            assert!(neighbours.record_pong(&peer(2), nonce).is_some());
+            pings = neighbours.next_pings(2, HOUR, HOUR);
+        }
+
+        assert_eq!(neighbours.alive(), vec![peer(2)]);
+        assert_eq!(neighbours.list(), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn dead_neighbours_are_only_probed_every_dead_probe_interval() {
+        let neighbours = Neighbours::new(&[peer(1)], 8);
+        kill(&neighbours, peer(1), 1);
+
+        assert!(neighbours.next_pings(1, HOUR, HOUR).is_empty());
+        assert!(neighbours.next_pings(1, HOUR, HOUR).is_empty());
+
+        let probes = neighbours.next_pings(1, Duration::ZERO, HOUR);
+        assert_eq!(probes.len(), 1);
+        assert_eq!(probes[0].0, peer(1));
+        assert!(neighbours.alive().is_empty());
+    }
+
+    #[test]
+    fn matching_pong_revives_a_dead_neighbour() {
+        let neighbours = Neighbours::new(&[peer(1)], 8);
+        kill(&neighbours, peer(1), 1);
+        let (_address, nonce) = neighbours.next_pings(1, Duration::ZERO, HOUR)[0];
+
+        assert!(neighbours.record_pong(&peer(1), nonce).is_some());
+
+        assert_eq!(neighbours.alive(), vec![peer(1)]);
+        // The miss count starts over as well.
+        neighbours.next_pings(1, HOUR, HOUR);
+        assert_eq!(neighbours.alive(), vec![peer(1)]);
+    }
+
+    #[test]
+    fn pongs_with_another_nonce_are_ignored() {
+        let neighbours = Neighbours::new(&[peer(1)], 8);
+        let (_address, nonce) = neighbours.next_pings(1, HOUR, HOUR)[0];
+
+        assert_eq!(
+            neighbours.record_pong(&peer(1), nonce.wrapping_add(1)),
+            None
+        );
+        assert_eq!(neighbours.record_pong(&peer(2), nonce), None);
+
+        neighbours.next_pings(1, HOUR, HOUR);
+        assert!(neighbours.alive().is_empty());
+    }
+
+    #[test]
+    fn pong_is_only_counted_once() {
+        let neighbours = Neighbours::new(&[peer(1)], 8);
+        let (_address, nonce) = neighbours.next_pings(1, HOUR, HOUR)[0];
+
+        assert!(neighbours.record_pong(&peer(1), nonce).is_some());
+        assert_eq!(neighbours.record_pong(&peer(1), nonce), None);
+    }
+
+    #[test]
+    fn round_trip_time_is_smoothed() {
+        let neighbours = Neighbours::new(&[peer(1)], 8);
+        neighbours.neighbours.write().unwrap()[0].rtt = Some(Duration::from_secs(8));
+        let (_address, nonce) = neighbours.next_pings(1, HOUR, HOUR)[0];
+
+        let rtt = neighbours.record_pong(&peer(1), nonce).unwrap();
+
+        assert!(
+            rtt > Duration::from_secs(7)
+                && rtt <= Duration::from_secs(7) + Duration::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn joining_again_revives_a_dead_neighbour() {
+        let neighbours = Neighbours::new(&[peer(1)], 1);
+        kill(&neighbours, peer(1), 1);
+
+        assert!(neighbours.add_if_room(peer(2)));
+        assert!(neighbours.admit(peer(1)));
+
+        assert_eq!(sorted(neighbours.alive()), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn neighbours_dead_for_the_timeout_are_forgotten() {
+        let neighbours = Neighbours::new(&[peer(1), peer(2)], 8);
+        kill(&neighbours, peer(1), 1);
+        assert_eq!(neighbours.list(), vec![peer(1), peer(2)]);
+
+        neighbours.next_pings(1, HOUR, Duration::ZERO);
+
+        assert!(!neighbours.contains(&peer(1)));
+    }
+
+    #[test]
+    fn longest_dead_neighbour_makes_room_in_a_full_table() {
+        let neighbours = Neighbours::new(&[peer(1), peer(2)], 1);
+        // Nobody answers, so both die in the same rounds.
+        kill(&neighbours, peer(1), 1);
+        assert!(neighbours.alive().is_empty());
+
+        assert!(neighbours.add_if_room(peer(3)));
+        assert_eq!(neighbours.list().len(), 2);
+
+        assert!(!neighbours.add_if_room(peer(4)));
+        assert!(neighbours.list().contains(&peer(3)));
+    }
+}
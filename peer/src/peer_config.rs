@@ -1,35 +1,329 @@
-use std::{env, net::SocketAddr};
+use common::NodeId;
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use crate::chunk_store::StoreKind;
+use crate::forwarding::ForwardingStrategy;
+use crate::manifest::ParseMode;
 
 #[derive(Debug)]
 pub struct PeerConfig {
     pub address: SocketAddr,
-    pub kv_file_path: String,
+    /// Key-values file, chunk directory or archive, depending on `store`.
+    pub store_path: String,
+    pub store: StoreKind,
+    /// Key-values file whose chunks are copied into the store on startup.
+    pub import_path: Option<String>,
+    pub manifest_mode: ParseMode,
+    /// How often the store and the watched paths are checked for changes. `None` leaves
+    /// reloads to SIGHUP only.
+    pub reload_interval: Option<Duration>,
+    /// Extra paths whose changes trigger a reload, such as the directory of the chunk files.
+    pub watched_paths: Vec<String>,
+    /// Neighbours the peer starts with.
     pub known_peers: Vec<SocketAddr>,
+    /// Peers sent a Join on startup, to enter a running swarm.
+    pub bootstrap_peers: Vec<SocketAddr>,
+    /// Amount of neighbours above which peers learned through peer exchange are ignored.
+    pub max_neighbours: usize,
+    /// How often the neighbours are pinged. `None` disables keepalives.
+    pub ping_interval: Option<Duration>,
+    /// Amount of pongs in a row a neighbour may miss before it is marked dead.
+    pub max_missed_pongs: u32,
+    /// Tracker the chunks are announced to. Without one, chunks are only found by flooding.
+    pub tracker: Option<SocketAddr>,
+    /// How often chunks are announced to the tracker or published in the DHT.
+    pub announce_interval: Duration,
+    /// Whether the peer takes part in the DHT, under `node_id` or a random ID.
+    pub dht: bool,
+    pub node_id: Option<NodeId>,
+    /// TTL of the queries started by this peer when the client does not ask for one.
+    pub query_ttl: u16,
+    /// Upper bound for the TTL a client may ask for in its Hello.
+    pub max_query_ttl: u16,
+    pub forwarding: ForwardingStrategy,
+    /// Amount of threads sending chunks to clients.
+    pub workers: usize,
+    /// Maximum amount of chunk bytes kept in memory.
+    pub cache_size: usize,
 }
 
 impl PeerConfig {
-    pub fn new(mut args: env::Args) -> PeerConfig {
+    /// The configuration of a peer at `address` serving the chunks of the key-values file at
+    /// `store_path`, with every other setting at its default.
+    pub fn with_defaults(address: SocketAddr, store_path: String) -> PeerConfig {
+        PeerConfig {
+            address,
+            store_path,
+            store: StoreKind::KvFile,
+            import_path: None,
+            manifest_mode: ParseMode::Strict,
+            reload_interval: interval(2),
+            watched_paths: Vec::new(),
+            known_peers: Vec::new(),
+            bootstrap_peers: Vec::new(),
+            max_neighbours: 8,
+            ping_interval: interval(5),
+            max_missed_pongs: 3,
+            tracker: None,
+            announce_interval: Duration::from_secs(30),
+            dht: false,
+            node_id: None,
+            query_ttl: 3,
+            max_query_ttl: 8,
+            forwarding: ForwardingStrategy::Flood,
+            workers: 4,
+            cache_size: 64 * 1024 * 1024,
+        }
+    }
+
+    /// Parses `<address> <store path> [known peers...]`, optionally mixed with `--ttl <n>`,
+    /// `--max-ttl <n>`, `--forward <flood|random:k>`, `--workers <n>`, `--cache-size <MiB>`,
+    /// `--store <kv|dir|archive|memory>`, `--import <key-values file>`, `--skip-bad-entries`,
+    /// `--reload-interval <seconds>` (0 disables polling), `--watch <path>`,
+    /// `--bootstrap <address>`, `--max-neighbours <n>`, `--ping-interval <seconds>` (0 disables
+    /// keepalives), `--max-missed-pongs <n>`, `--tracker <address>`,
+    /// `--announce-interval <seconds>`, `--dht` and `--node-id <n>`. The first argument is the
+    /// program name, as in `env::args()`.
+    pub fn new<I: Iterator<Item = String>>(mut args: I) -> Result<PeerConfig, String> {
         args.next();
 
-        let address = args
-            .next()
-            .expect("Address not specified")
+        let unspecified = SocketAddr::from(([0, 0, 0, 0], 0));
+        let mut config = PeerConfig::with_defaults(unspecified, String::new());
+        let mut positional = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--ttl" => config.query_ttl = parse_value(&mut args, "TTL")?,
+                "--max-ttl" => config.max_query_ttl = parse_value(&mut args, "maximum TTL")?,
+                "--forward" => config.forwarding = parse_value(&mut args, "forwarding strategy")?,
+                "--workers" => {
+                    let workers: usize = parse_value(&mut args, "amount of workers")?;
+                    config.workers = workers.max(1);
+                }
+                "--cache-size" => {
+                    let cache_size_mib: usize = parse_value(&mut args, "cache size")?;
+                    config.cache_size = cache_size_mib.saturating_mul(1024 * 1024);
+                }
+                "--store" => config.store = parse_value(&mut args, "store")?,
+                "--import" => config.import_path = Some(parse_value(&mut args, "import path")?),
+                "--skip-bad-entries" => config.manifest_mode = ParseMode::SkipBadEntries,
+                "--reload-interval" => {
+                    config.reload_interval = interval(parse_value(&mut args, "reload interval")?)
+                }
+                "--watch" => config
+                    .watched_paths
+                    .push(parse_value(&mut args, "watched path")?),
+                "--bootstrap" => config
+                    .bootstrap_peers
+                    .push(parse_value(&mut args, "bootstrap address")?),
+                "--max-neighbours" => {
+                    config.max_neighbours = parse_value(&mut args, "maximum amount of neighbours")?
+                }
+                "--ping-interval" => {
+                    config.ping_interval = interval(parse_value(&mut args, "ping interval")?)
+                }
+                "--max-missed-pongs" => {
+                    let max_missed_pongs: u32 = parse_value(&mut args, "maximum missed pongs")?;
+                    config.max_missed_pongs = max_missed_pongs.max(1);
+                }
+                "--tracker" => config.tracker = Some(parse_value(&mut args, "tracker address")?),
+                "--announce-interval" => {
+                    let seconds: u64 = parse_value(&mut args, "announce interval")?;
+                    config.announce_interval = Duration::from_secs(seconds.max(1));
+                }
+                "--dht" => config.dht = true,
+                "--node-id" => config.node_id = Some(parse_value(&mut args, "node ID")?),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+
+        let address = positional.next().ok_or("Address not specified")?;
+        config.address = address
             .parse()
-            .expect("Unable to parse IP");
+            .map_err(|_| format!("Unable to parse IP {}", address))?;
 
-        let kv_file_path = args.next().expect("Key-values file path not specified");
+        config.store_path = positional.next().ok_or("Store path not specified")?;
 
-        let mut known_peers = Vec::new();
-        while let Some(addr) = args.next() {
-            let peer_address: SocketAddr = addr.parse().expect("Failed to parse address");
+        for addr in positional {
+            let peer_address: SocketAddr = addr
+                .parse()
+                .map_err(|_| format!("Failed to parse address {}", addr))?;
 
-            known_peers.push(peer_address);
+            config.known_peers.push(peer_address);
         }
 
-        return PeerConfig {
-            address,
-            kv_file_path,
-            known_peers,
-        };
+        config.check_address_families()?;
+
+        Ok(config)
+    }
+
+    /// Whether the store can be rebuilt from `store_path` alone. Memory stores and the chunks
+    /// copied in with `--import` only exist in the running peer, so reloading would lose them.
+    pub fn can_reload_store(&self) -> bool {
+        self.store != StoreKind::Memory && self.import_path.is_none()
+    }
+
+    /// A socket bound to an IPv4 address cannot send to IPv6 hosts, so such peers would fail
+    /// on every send. IPv6 sockets reach IPv4 hosts through mapped addresses.
+    fn check_address_families(&self) -> Result<(), String> {
+        if self.address.is_ipv6() {
+            return Ok(());
+        }
+
+        let ipv6_peer = self
+            .known_peers
+            .iter()
+            .chain(self.bootstrap_peers.iter())
+            .chain(self.tracker.iter())
+            .find(|address| address.is_ipv6());
+        match ipv6_peer {
+            Some(address) => Err(format!(
+                "{} is an IPv6 address but the peer is bound to IPv4 address {}",
+                address, self.address
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// TTL for a query started on behalf of a client that asked for `requested_ttl`.
+    pub fn query_ttl_for(&self, requested_ttl: u16) -> u16 {
+        if requested_ttl == 0 {
+            self.query_ttl
+        } else {
+            requested_ttl.min(self.max_query_ttl)
+        }
+    }
+}
+
+/// An interval of `seconds`, where 0 disables whatever runs at that interval.
+fn interval(seconds: u64) -> Option<Duration> {
+    Some(Duration::from_secs(seconds)).filter(|interval| !interval.is_zero())
+}
+
+/// Parses the value following an option.
+fn parse_value<T: FromStr, I: Iterator<Item = String>>(
+    args: &mut I,
+    name: &str,
+) -> Result<T, String> {
+    let value = args
+        .next()
+        .ok_or_else(|| format!("Value of {} not specified", name))?;
+
+    value
+        .parse()
+        .map_err(|_| format!("Unable to parse {} '{}'", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<PeerConfig, String> {
+        PeerConfig::new(
+            std::iter::once("peer")
+                .chain(args.split_whitespace())
+                .map(String::from),
+        )
+    }
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn defaults_serve_a_key_values_file() {
+        let config = PeerConfig::with_defaults(address(7000), "peer1.txt".to_string());
+
+        assert_eq!(config.address, address(7000));
+        assert_eq!(config.store_path, "peer1.txt");
+        assert_eq!(config.store, StoreKind::KvFile);
+        assert_eq!(config.import_path, None);
+        assert_eq!(config.reload_interval, Some(Duration::from_secs(2)));
+        assert_eq!(config.ping_interval, Some(Duration::from_secs(5)));
+        assert!(config.known_peers.is_empty());
+        assert!(config.bootstrap_peers.is_empty());
+        assert_eq!(config.tracker, None);
+        assert!(!config.dht);
+        assert_eq!(config.forwarding, ForwardingStrategy::Flood);
+        assert!(config.can_reload_store());
+    }
+
+    #[test]
+    fn positional_arguments_are_address_store_and_known_peers() {
+        let config = parse("127.0.0.1:7000 peer1.txt 127.0.0.1:7001 127.0.0.1:7002").unwrap();
+
+        assert_eq!(config.address, address(7000));
+        assert_eq!(config.store_path, "peer1.txt");
+        assert_eq!(config.known_peers, vec![address(7001), address(7002)]);
+    }
+
+    #[test]
+    fn options_mix_with_positional_arguments() {
+        let config = parse(
+            "--ttl 5 127.0.0.1:7000 --workers 0 --cache-size 2 chunks --store dir \
+             --forward random:2 --reload-interval 0 --bootstrap 127.0.0.1:7001 \
+             --tracker 127.0.0.1:9000 --announce-interval 0 --dht --node-id 9",
+        )
+        .unwrap();
+
+        assert_eq!(config.address, address(7000));
+        assert_eq!(config.store_path, "chunks");
+        assert_eq!(config.query_ttl, 5);
+        assert_eq!(config.workers, 1);
+        assert_eq!(config.cache_size, 2 * 1024 * 1024);
+        assert_eq!(config.store, StoreKind::Directory);
+        assert_eq!(config.forwarding, ForwardingStrategy::RandomNeighbours(2));
+        assert_eq!(config.reload_interval, None);
+        assert_eq!(config.bootstrap_peers, vec![address(7001)]);
+        assert_eq!(config.tracker, Some(address(9000)));
+        assert_eq!(config.announce_interval, Duration::from_secs(1));
+        assert!(config.dht);
+        assert_eq!(config.node_id, Some(9));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert_eq!(parse("").unwrap_err(), "Address not specified");
+        assert_eq!(
+            parse("127.0.0.1:7000").unwrap_err(),
+            "Store path not specified"
+        );
+        assert_eq!(
+            parse("not-an-address peer1.txt").unwrap_err(),
+            "Unable to parse IP not-an-address"
+        );
+        assert_eq!(
+            parse("127.0.0.1:7000 peer1.txt --ttl").unwrap_err(),
+            "Value of TTL not specified"
+        );
+        assert_eq!(
+            parse("127.0.0.1:7000 peer1.txt --workers many").unwrap_err(),
+            "Unable to parse amount of workers 'many'"
+        );
+        assert!(parse("127.0.0.1:7000 peer1.txt [::1]:7001").is_err());
+    }
+
+    #[test]
+    fn only_stores_rebuilt_from_disk_can_reload() {
+        let memory = parse("127.0.0.1:7000 unused --store memory").unwrap();
+        let imported = parse("127.0.0.1:7000 chunks --store dir --import peer1.txt").unwrap();
+
+        assert!(!memory.can_reload_store());
+        assert!(!imported.can_reload_store());
+    }
+
+    #[test]
+    fn query_ttl_defaults_and_is_clamped() {
+        let mut config =
+            PeerConfig::with_defaults(SocketAddr::from(([127, 0, 0, 1], 7000)), String::new());
+        config.query_ttl = 3;
+        config.max_query_ttl = 8;
+
+        assert_eq!(config.query_ttl_for(0), 3);
+        assert_eq!(config.query_ttl_for(1), 1);
+        assert_eq!(config.query_ttl_for(8), 8);
+        assert_eq!(config.query_ttl_for(200), 8);
     }
 }
@@ -0,0 +1,184 @@
+use common::NodeId;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use crate::random;
+
+/// Providers of the chunks whose keys are close to this node, as published by them. Records
+/// are dropped unless renewed within `lifetime`. At most `max_providers_per_key` providers are
+/// kept per key and `max_records` records in total, so that publishers cannot grow the table
+/// without bound.
+pub struct ProviderRecords {
+    records: HashMap<NodeId, HashMap<SocketAddr, Instant>>,
+    /// Amount of records over all keys.
+    len: usize,
+    lifetime: Duration,
+    max_providers_per_key: usize,
+    max_records: usize,
+}
+
+impl ProviderRecords {
+    pub fn new(
+        lifetime: Duration,
+        max_providers_per_key: usize,
+        max_records: usize,
+    ) -> ProviderRecords {
+        ProviderRecords {
+            records: HashMap::new(),
+            len: 0,
+            lifetime,
+            max_providers_per_key,
+            max_records,
+        }
+    }
+
+    /// Records that `provider` holds the chunk of `key`, or renews the record. Returns false
+    /// if the record is new and there is no room for it.
+    pub fn add(&mut self, key: NodeId, provider: SocketAddr) -> bool {
+        let expires_at = Instant::now() + self.lifetime;
+        if let Some(known) = self
+            .records
+            .get_mut(&key)
+            .and_then(|providers| providers.get_mut(&provider))
+        {
+            *known = expires_at;
+            return true;
+        }
+
+        let providers = self.records.entry(key).or_default();
+        if self.len >= self.max_records || providers.len() >= self.max_providers_per_key {
+            if providers.is_empty() {
+                self.records.remove(&key);
+            }
+            return false;
+        }
+
+        providers.insert(provider, expires_at);
+        self.len += 1;
+        true
+    }
+
+    /// Drops the records that were not renewed in time.
+    pub fn expire(&mut self) {
+        let now = Instant::now();
+        for providers in self.records.values_mut() {
+            providers.retain(|_provider, expires_at| *expires_at > now);
+        }
+        self.records.retain(|_key, providers| !providers.is_empty());
+        self.len = self.records.values().map(HashMap::len).sum();
+    }
+
+    /// At most `count` of the providers of `key`, picked at random so that every provider of a
+    /// popular chunk gets its share of the requests.
+    pub fn providers(&mut self, key: NodeId, count: usize) -> Vec<SocketAddr> {
+        let now = Instant::now();
+        let providers = match self.records.get_mut(&key) {
+            Some(providers) => providers,
+            None => return Vec::new(),
+        };
+
+        let before = providers.len();
+        providers.retain(|_provider, expires_at| *expires_at > now);
+        self.len -= before - providers.len();
+        let mut alive: Vec<SocketAddr> = providers.keys().copied().collect();
+        if alive.is_empty() {
+            self.records.remove(&key);
+        }
+
+        let count = count.min(alive.len());
+        for i in 0..count {
+            let j = i + (random::random_u64() as usize) % (alive.len() - i);
+            alive.swap(i, j);
+        }
+        alive.truncate(count);
+        alive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn provider(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn providers_are_listed_by_key() {
+        let mut records = ProviderRecords::new(Duration::from_secs(60), 100, 1000);
+        records.add(1, provider(1));
+        records.add(1, provider(2));
+        records.add(1, provider(1));
+        records.add(2, provider(3));
+
+        let mut providers = records.providers(1, 10);
+        providers.sort();
+        assert_eq!(providers, vec![provider(1), provider(2)]);
+        assert_eq!(records.providers(2, 10), vec![provider(3)]);
+        assert!(records.providers(3, 10).is_empty());
+    }
+
+    #[test]
+    fn providers_are_capped_at_count() {
+        let mut records = ProviderRecords::new(Duration::from_secs(60), 100, 1000);
+        for port in 0..100 {
+            records.add(1, provider(port));
+        }
+
+        let mut providers = records.providers(1, 8);
+        assert_eq!(providers.len(), 8);
+        providers.sort();
+        providers.dedup();
+        assert_eq!(providers.len(), 8);
+    }
+
+    #[test]
+    fn records_expire_unless_renewed() {
+        let mut records = ProviderRecords::new(Duration::from_millis(50), 100, 1000);
+        records.add(1, provider(1));
+        records.add(1, provider(2));
+        records.add(2, provider(1));
+        thread::sleep(Duration::from_millis(30));
+        records.add(1, provider(2));
+        thread::sleep(Duration::from_millis(30));
+
+        assert_eq!(records.providers(1, 10), vec![provider(2)]);
+
+        records.expire();
+        assert!(!records.records.contains_key(&2));
+        assert!(records.providers(2, 10).is_empty());
+    }
+
+    #[test]
+    fn providers_beyond_the_cap_per_key_are_refused() {
+        let mut records = ProviderRecords::new(Duration::from_secs(60), 2, 1000);
+
+        assert!(records.add(1, provider(1)));
+        assert!(records.add(1, provider(2)));
+        assert!(!records.add(1, provider(3)));
+        assert!(records.add(1, provider(1)));
+        assert!(records.add(2, provider(3)));
+
+        assert_eq!(records.providers(1, 10).len(), 2);
+    }
+
+    #[test]
+    fn records_beyond_the_total_cap_are_refused_until_some_expire() {
+        let mut records = ProviderRecords::new(Duration::from_millis(50), 100, 3);
+        for key in 0..3 {
+            assert!(records.add(key, provider(1)));
+        }
+
+        assert!(!records.add(3, provider(1)));
+        assert!(!records.records.contains_key(&3));
+
+        thread::sleep(Duration::from_millis(60));
+        records.expire();
+        assert!(records.add(3, provider(1)));
+        assert_eq!(records.len, 1);
+    }
+}
@@ -0,0 +1,172 @@
+use common::{Contact, NodeId};
+use std::{collections::HashMap, net::SocketAddr};
+
+/// Contacts kept per bucket, and the amount of closest nodes lookups converge on.
+pub const BUCKET_SIZE: usize = 8;
+/// Queries in a row a contact may leave unanswered before it is forgotten, so that a single
+/// lost datagram does not evict it.
+pub const MAX_FAILURES: u32 = 3;
+
+/// Kademlia routing table. Bucket `i` holds contacts whose XOR distance to this node has its
+/// highest set bit at position `i`, so every bucket covers twice the ID space of the previous
+/// one, and the node knows more nodes close to it than far from it.
+pub struct RoutingTable {
+    own_id: NodeId,
+    /// Least recently seen contact first.
+    buckets: Vec<Vec<Contact>>,
+    /// Queries left unanswered in a row, by contact address.
+    failures: HashMap<SocketAddr, u32>,
+}
+
+impl RoutingTable {
+    pub fn new(own_id: NodeId) -> RoutingTable {
+        RoutingTable {
+            own_id,
+            buckets: vec![Vec::new(); 64],
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Records that `contact` was seen. Known contacts move to the end of their bucket. New
+    /// ones are dropped if the bucket is full, as long-lived nodes are the likeliest to stay.
+    pub fn observe(&mut self, contact: Contact) {
+        let bucket = match self.bucket_index(contact.id) {
+            Some(bucket) => &mut self.buckets[bucket],
+            None => return,
+        };
+
+        self.failures.remove(&contact.address);
+
+        // A node coming back with another ID or address replaces its old entry.
+        bucket.retain(|known| known.id != contact.id && known.address != contact.address);
+        if bucket.len() < BUCKET_SIZE {
+            bucket.push(contact);
+        }
+    }
+
+    /// Records that the contact at `address` did not answer a query, forgetting it after
+    /// `MAX_FAILURES` in a row. Observing the contact again resets the count.
+    pub fn record_failure(&mut self, address: &SocketAddr) {
+        let known = self
+            .buckets
+            .iter()
+            .flatten()
+            .any(|contact| contact.address == *address);
+        if !known {
+            return;
+        }
+
+        let failures = self.failures.entry(*address).or_insert(0);
+        *failures += 1;
+        if *failures < MAX_FAILURES {
+            return;
+        }
+
+        self.failures.remove(address);
+        for bucket in &mut self.buckets {
+            bucket.retain(|known| known.address != *address);
+        }
+    }
+
+    /// The `count` known contacts closest to `target`.
+    pub fn closest(&self, target: NodeId, count: usize) -> Vec<Contact> {
+        let mut contacts: Vec<Contact> = self.buckets.iter().flatten().copied().collect();
+        contacts.sort_by_key(|contact| common::distance(contact.id, target));
+        contacts.truncate(count);
+
+        contacts
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    /// `None` for this node's own ID.
+    fn bucket_index(&self, id: NodeId) -> Option<usize> {
+        let distance = common::distance(self.own_id, id);
+        if distance == 0 {
+            return None;
+        }
+
+        Some(63 - distance.leading_zeros() as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(id: NodeId) -> Contact {
+        Contact {
+            id,
+            address: SocketAddr::from(([127, 0, 0, 1], id as u16)),
+        }
+    }
+
+    #[test]
+    fn bucket_index_is_the_highest_differing_bit() {
+        let table = RoutingTable::new(0b1000);
+
+        assert_eq!(table.bucket_index(0b1000), None);
+        assert_eq!(table.bucket_index(0b1001), Some(0));
+        assert_eq!(table.bucket_index(0b1010), Some(1));
+        assert_eq!(table.bucket_index(0b0000), Some(3));
+        assert_eq!(table.bucket_index(0b0111), Some(3));
+        assert_eq!(table.bucket_index(u64::MAX), Some(63));
+    }
+
+    #[test]
+    fn closest_sorts_by_xor_distance() {
+        let mut table = RoutingTable::new(0);
+        for id in [1, 2, 3, 12, 13, 40] {
+            table.observe(contact(id));
+        }
+
+        let closest: Vec<NodeId> = table.closest(9, 3).iter().map(|c| c.id).collect();
+        assert_eq!(closest, vec![13, 12, 1]);
+        assert_eq!(table.closest(9, 100).len(), 6);
+    }
+
+    #[test]
+    fn own_id_and_full_buckets_are_not_added() {
+        let mut table = RoutingTable::new(0);
+        table.observe(contact(0));
+        // Every ID in 256..512 falls in bucket 8.
+        for id in 256..256 + BUCKET_SIZE as NodeId + 2 {
+            table.observe(contact(id));
+        }
+
+        assert_eq!(table.len(), BUCKET_SIZE);
+        assert!(table
+            .closest(256 + BUCKET_SIZE as NodeId, 1)
+            .iter()
+            .all(|c| c.id < 256 + BUCKET_SIZE as NodeId));
+    }
+
+    #[test]
+    fn contact_is_forgotten_after_failures_in_a_row() {
+        let mut table = RoutingTable::new(0);
+        table.observe(contact(1));
+
+        for _ in 1..MAX_FAILURES {
+            table.record_failure(&contact(1).address);
+        }
+        assert_eq!(table.len(), 1);
+
+        table.record_failure(&contact(1).address);
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn observing_a_contact_resets_its_failures() {
+        let mut table = RoutingTable::new(0);
+        table.observe(contact(1));
+
+        for _ in 0..MAX_FAILURES * 2 {
+            table.record_failure(&contact(1).address);
+            table.observe(contact(1));
+        }
+
+        assert_eq!(table.len(), 1);
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Remembers recently handled queries, identified by the address of the client that asked
+/// and the query ID, so that each query is answered and forwarded at most once. Queries are
+/// forgotten after `retention`, or oldest first once `capacity` of them are remembered.
+pub struct SeenQueries {
+    queries: HashSet<(SocketAddr, u32)>,
+    /// The queries in `queries` with the time they were first seen, oldest first.
+    order: VecDeque<(Instant, (SocketAddr, u32))>,
+    retention: Duration,
+    capacity: usize,
+}
+
+impl SeenQueries {
+    pub fn new(retention: Duration, capacity: usize) -> SeenQueries {
+        SeenQueries {
+            queries: HashSet::new(),
+            order: VecDeque::new(),
+            retention,
+            capacity,
+        }
+    }
+
+    /// Records the query and returns whether it is the first time it was seen.
+    pub fn insert(&mut self, origin: SocketAddr, query_id: u32) -> bool {
+        while let Some((seen_at, query)) = self.order.front() {
+            if seen_at.elapsed() < self.retention {
+                break;
+            }
+            self.queries.remove(query);
+            self.order.pop_front();
+        }
+
+        let query = (origin, query_id);
+        if self.queries.contains(&query) {
+            return false;
+        }
+
+        if self.order.len() >= self.capacity {
+            if let Some((_seen_at, oldest)) = self.order.pop_front() {
+                self.queries.remove(&oldest);
+            }
+        }
+        self.queries.insert(query);
+        self.order.push_back((Instant::now(), query));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn client(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn query_is_only_new_the_first_time() {
+        let mut seen = SeenQueries::new(Duration::from_secs(60), 100);
+
+        assert!(seen.insert(client(1), 7));
+        assert!(!seen.insert(client(1), 7));
+    }
+
+    #[test]
+    fn queries_are_told_apart_by_origin_and_id() {
+        let mut seen = SeenQueries::new(Duration::from_secs(60), 100);
+
+        assert!(seen.insert(client(1), 7));
+        assert!(seen.insert(client(1), 8));
+        assert!(seen.insert(client(2), 7));
+    }
+
+    #[test]
+    fn queries_are_forgotten_after_the_retention() {
+        let mut seen = SeenQueries::new(Duration::from_millis(20), 100);
+        seen.insert(client(1), 7);
+        seen.insert(client(2), 7);
+
+        thread::sleep(Duration::from_millis(30));
+
+        assert!(seen.insert(client(1), 7));
+        assert_eq!(seen.queries.len(), 1);
+        assert_eq!(seen.order.len(), 1);
+    }
+
+    #[test]
+    fn oldest_queries_are_forgotten_beyond_the_capacity() {
+        let mut seen = SeenQueries::new(Duration::from_secs(60), 2);
+        seen.insert(client(1), 1);
+        seen.insert(client(1), 2);
+        seen.insert(client(1), 3);
+
+        assert_eq!(seen.queries.len(), 2);
+        assert!(!seen.insert(client(1), 3));
+        assert!(seen.insert(client(1), 1));
+    }
+}
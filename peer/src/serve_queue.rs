@@ -0,0 +1,136 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Condvar, Mutex},
+};
+
+/// Amount of jobs a single client may have waiting. Clients re-request whatever was dropped
+/// once their requests stall.
+pub const MAX_JOBS_PER_CLIENT: usize = 4096;
+
+/// Job queue shared by the serving workers. Jobs are kept per client and handed out in
+/// round-robin order between clients, so a client asking for many chunks at once only delays
+/// the others by one job at a time.
+pub struct ServeQueue<T> {
+    state: Mutex<QueueState<T>>,
+    available: Condvar,
+    max_jobs_per_client: usize,
+}
+
+struct QueueState<T> {
+    jobs: HashMap<SocketAddr, VecDeque<T>>,
+    turns: VecDeque<SocketAddr>,
+}
+
+impl<T> ServeQueue<T> {
+    pub fn new(max_jobs_per_client: usize) -> ServeQueue<T> {
+        ServeQueue {
+            state: Mutex::new(QueueState {
+                jobs: HashMap::new(),
+                turns: VecDeque::new(),
+            }),
+            available: Condvar::new(),
+            max_jobs_per_client,
+        }
+    }
+
+    /// Queues `job`, returning false if it was dropped because `client` already has the
+    /// maximum amount of jobs waiting.
+    pub fn push(&self, client: SocketAddr, job: T) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        let client_jobs = state.jobs.entry(client).or_default();
+        if client_jobs.len() >= self.max_jobs_per_client {
+            return false;
+        }
+        client_jobs.push_back(job);
+        if client_jobs.len() == 1 {
+            state.turns.push_back(client);
+        }
+
+        self.available.notify_one();
+        true
+    }
+
+    /// Blocks until there is a job, and returns the next one of the client whose turn it is.
+    pub fn pop(&self) -> (SocketAddr, T) {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(client) = state.turns.pop_front() {
+                let client_jobs = state.jobs.get_mut(&client).expect("Client without jobs");
+                let job = client_jobs.pop_front().expect("Client without jobs");
+
+                if client_jobs.is_empty() {
+                    state.jobs.remove(&client);
+                } else {
+                    state.turns.push_back(client);
+                }
+
+                return (client, job);
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn clients_take_turns() {
+        let queue = ServeQueue::new(16);
+        for job in 0..3 {
+            queue.push(client(1), job);
+        }
+        queue.push(client(2), 10);
+        queue.push(client(3), 20);
+        queue.push(client(2), 11);
+
+        let order: Vec<(SocketAddr, i32)> = (0..6).map(|_| queue.pop()).collect();
+
+        assert_eq!(
+            order,
+            vec![
+                (client(1), 0),
+                (client(2), 10),
+                (client(3), 20),
+                (client(1), 1),
+                (client(2), 11),
+                (client(1), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn client_rejoins_the_rotation_after_draining_its_jobs() {
+        let queue = ServeQueue::new(16);
+        queue.push(client(1), 0);
+        assert_eq!(queue.pop(), (client(1), 0));
+
+        queue.push(client(2), 10);
+        queue.push(client(1), 1);
+
+        assert_eq!(queue.pop(), (client(2), 10));
+        assert_eq!(queue.pop(), (client(1), 1));
+    }
+
+    #[test]
+    fn jobs_beyond_the_per_client_limit_are_dropped() {
+        let queue = ServeQueue::new(2);
+
+        assert!(queue.push(client(1), 0));
+        assert!(queue.push(client(1), 1));
+        assert!(!queue.push(client(1), 2));
+        assert!(queue.push(client(2), 10));
+
+        assert_eq!(queue.pop(), (client(1), 0));
+        assert!(queue.push(client(1), 3));
+    }
+}
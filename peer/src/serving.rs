@@ -0,0 +1,154 @@
+use common::{Message, ResponseInfo};
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use crate::catalogue::Catalogue;
+use crate::chunk_manager::{ChunkKey, ChunkManager};
+use crate::error_stats::ErrorStats;
+use crate::serve_queue::ServeQueue;
+
+pub enum ServeJob {
+    /// Every fragment of a chunk, asked for in a GET.
+    Chunk(ChunkKey),
+    /// Some fragments of a chunk, asked for in a GET FRAGMENTS.
+    Fragments(ChunkKey, Vec<u16>),
+}
+
+/// Starts `count` workers sending the chunks requested through `queue`.
+pub fn spawn_workers(
+    count: usize,
+    queue: Arc<ServeQueue<ServeJob>>,
+    catalogue: Arc<Catalogue>,
+    udp_socket: Arc<UdpSocket>,
+    error_stats: Arc<ErrorStats>,
+) -> Vec<JoinHandle<()>> {
+    (0..count)
+        .map(|_| {
+            let queue = queue.clone();
+            let catalogue = catalogue.clone();
+            let udp_socket = udp_socket.clone();
+            let error_stats = error_stats.clone();
+
+            thread::spawn(move || loop {
+                let (remote_address, job) = queue.pop();
+                // The job keeps the catalogue it started with, even if a reload happens
+                // while it is being served.
+                serve(
+                    &catalogue.current(),
+                    &udp_socket,
+                    job,
+                    &remote_address,
+                    &error_stats,
+                );
+            })
+        })
+        .collect()
+}
+
+fn serve(
+    chunk_manager: &ChunkManager,
+    udp_socket: &UdpSocket,
+    job: ServeJob,
+    remote_address: &SocketAddr,
+    error_stats: &ErrorStats,
+) {
+    match job {
+        ServeJob::Chunk(key) => {
+            if let Some(chunk_data) = chunk_manager.get(&key) {
+                println!("Sending chunk {} to client {}", key, remote_address);
+                for response_message in
+                    ResponseInfo::from_chunk(&key.content, key.chunk_id, &chunk_data)
+                {
+                    send_response(udp_socket, response_message, remote_address, error_stats);
+                }
+            }
+        }
+        ServeJob::Fragments(key, fragments) => {
+            let chunk_data = match chunk_manager.get(&key) {
+                Some(chunk_data) => chunk_data,
+                None => return,
+            };
+            let fragment_count = ResponseInfo::fragment_count(chunk_data.len()).unwrap_or(0);
+            let fragments = match requested_fragments(fragments, fragment_count) {
+                Some(fragments) => fragments,
+                None => {
+                    println!(
+                        "Ignoring request from {} for more fragments than chunk {} has",
+                        remote_address, key
+                    );
+                    return;
+                }
+            };
+
+            println!(
+                "Sending {} fragments of chunk {} to client {}",
+                fragments.len(),
+                key,
+                remote_address
+            );
+            for fragment_index in fragments {
+                if let Some(response_message) = ResponseInfo::from_chunk_fragment(
+                    &key.content,
+                    key.chunk_id,
+                    &chunk_data,
+                    fragment_index,
+                ) {
+                    send_response(udp_socket, response_message, remote_address, error_stats);
+                }
+            }
+        }
+    }
+}
+
+/// The distinct fragments asked for that a chunk of `fragment_count` fragments has, in order.
+/// A request for more fragments than the chunk has is refused as a whole, so that a small
+/// datagram cannot make the peer send a flood of Responses.
+fn requested_fragments(mut fragments: Vec<u16>, fragment_count: u16) -> Option<Vec<u16>> {
+    fragments.sort_unstable();
+    fragments.dedup();
+    if fragments.len() > usize::from(fragment_count) {
+        return None;
+    }
+
+    fragments.retain(|&index| index < fragment_count);
+    Some(fragments)
+}
+
+fn send_response(
+    udp_socket: &UdpSocket,
+    response_message: ResponseInfo,
+    remote_address: &SocketAddr,
+    error_stats: &ErrorStats,
+) {
+    let data = Message::Response(response_message).serialize();
+    if let Err(err) = crate::send_to(udp_socket, &data, *remote_address) {
+        error_stats.record_send_error(remote_address, &err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_fragments_are_sorted_and_deduplicated() {
+        assert_eq!(
+            requested_fragments(vec![3, 1, 3, 0, 1], 4),
+            Some(vec![0, 1, 3])
+        );
+    }
+
+    #[test]
+    fn fragments_the_chunk_lacks_are_dropped() {
+        assert_eq!(requested_fragments(vec![1, 7, 9], 4), Some(vec![1]));
+    }
+
+    #[test]
+    fn requests_for_more_fragments_than_the_chunk_has_are_refused() {
+        assert_eq!(requested_fragments((0..5).collect(), 4), None);
+        assert_eq!(requested_fragments((0..=u16::MAX).collect(), 100), None);
+    }
+}
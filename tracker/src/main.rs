@@ -0,0 +1,124 @@
+use common::{Message, ProviderInfo};
+use std::{
+    env,
+    net::{SocketAddr, UdpSocket},
+    process,
+    time::{Duration, Instant},
+};
+
+mod registry;
+use registry::Registry;
+
+mod tracker_config;
+use tracker_config::TrackerConfig;
+
+/// How often expired announcements are dropped.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps every TrackerReply within one datagram, whatever the encoding of its chunk list.
+const MAX_CHUNKS_PER_REPLY: usize = 8 * 1024;
+
+/// Keeps track of which peers hold which chunks. Peers announce their chunks periodically and
+/// say Bye when they leave; clients ask which peers hold the chunks they want and get one
+/// TrackerReply per provider.
+fn main() {
+    let config = TrackerConfig::new(env::args()).unwrap_or_else(|err| exit_with(&err));
+    let udp_socket = UdpSocket::bind(config.address)
+        .unwrap_or_else(|err| exit_with(&format!("Unable to bind to {}: {}", config.address, err)));
+    udp_socket
+        .set_read_timeout(Some(EXPIRY_CHECK_INTERVAL))
+        .unwrap_or_else(|err| exit_with(&format!("Unable to set read timeout: {}", err)));
+
+    println!("UDP bound to {}", config.address.port());
+
+    let mut registry = Registry::new(config.expiry);
+    let mut last_expiry_check = Instant::now();
+
+    loop {
+        if last_expiry_check.elapsed() >= EXPIRY_CHECK_INTERVAL {
+            registry.expire();
+            last_expiry_check = Instant::now();
+        }
+
+        let mut buffer = [0; 60 * 1024];
+        let (bytes_read, remote_address) = match udp_socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            // Timeouts only wake the loop up to expire announcements, and other errors are
+            // usually ICMP reports about replies sent to departed clients.
+            Err(_) => continue,
+        };
+        let remote_address = common::canonical_address(remote_address);
+
+        let message = match Message::new(&buffer, bytes_read) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!(
+                    "Dropping malformed message from {}: {}",
+                    remote_address, err
+                );
+                continue;
+            }
+        };
+        match message {
+            Message::Announce(data) => {
+                println!(
+                    "{} announced {} chunks of content {}",
+                    remote_address,
+                    data.chunk_list.chunks.len(),
+                    data.content
+                );
+                registry.announce(remote_address, data.content, data.chunk_list.chunks);
+            }
+            Message::AnnounceMore(data) => {
+                println!(
+                    "{} announced {} more chunks of content {}",
+                    remote_address,
+                    data.chunk_list.chunks.len(),
+                    data.content
+                );
+                registry.announce_more(remote_address, data.content, data.chunk_list.chunks);
+            }
+            Message::TrackerQuery(data) => {
+                let providers = registry.providers(&data.content, &data.chunk_list.chunks);
+                println!(
+                    "{} asked for {} chunks of content {}, {} providers found",
+                    remote_address,
+                    data.chunk_list.chunks.len(),
+                    data.content,
+                    providers.len()
+                );
+
+                for (provider, chunks) in providers {
+                    for batch in chunks.chunks(MAX_CHUNKS_PER_REPLY) {
+                        let reply = ProviderInfo::from_chunks(
+                            data.content.clone(),
+                            provider,
+                            batch.to_vec(),
+                        );
+                        send_or_log(&udp_socket, &reply.serialize(), remote_address);
+                    }
+                }
+            }
+            Message::Bye => {
+                println!("{} left", remote_address);
+                registry.remove_peer(&remote_address);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send_or_log(udp_socket: &UdpSocket, data: &[u8], address: SocketAddr) {
+    let target = match udp_socket.local_addr() {
+        Ok(local_address) => common::reachable_address(&local_address, address),
+        Err(_) => address,
+    };
+    if let Err(err) = udp_socket.send_to(data, target) {
+        eprintln!("Failed to communicate with {}: {}", address, err);
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
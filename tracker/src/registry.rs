@@ -0,0 +1,261 @@
+use common::{ChunkId, ContentId};
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Chunks tracked for a single peer over all contents. Anything it announces beyond that is
+/// ignored, so that no peer can grow the registry without bound.
+pub const MAX_CHUNKS_PER_PEER: usize = 256 * 1024;
+
+/// The chunks every peer announced, by content.
+pub struct Registry {
+    contents: HashMap<ContentId, HashMap<SocketAddr, Announcement>>,
+    expiry: Duration,
+}
+
+struct Announcement {
+    chunks: BTreeSet<ChunkId>,
+    expires_at: Instant,
+}
+
+impl Registry {
+    pub fn new(expiry: Duration) -> Registry {
+        Registry {
+            contents: HashMap::new(),
+            expiry,
+        }
+    }
+
+    /// Replaces what `peer` holds of `content`. An empty list withdraws the peer.
+    pub fn announce(&mut self, peer: SocketAddr, content: ContentId, chunks: Vec<ChunkId>) {
+        if let Some(providers) = self.contents.get_mut(&content) {
+            providers.remove(&peer);
+        }
+        self.announce_more(peer, content, chunks);
+    }
+
+    /// Adds `chunks` to what `peer` holds of `content`, renewing its announcement.
+    pub fn announce_more(&mut self, peer: SocketAddr, content: ContentId, chunks: Vec<ChunkId>) {
+        if chunks.is_empty() {
+            return;
+        }
+
+        let held: usize = self
+            .contents
+            .values()
+            .filter_map(|providers| providers.get(&peer))
+            .map(|announcement| announcement.chunks.len())
+            .sum();
+        let mut room = MAX_CHUNKS_PER_PEER.saturating_sub(held);
+
+        let expires_at = Instant::now() + self.expiry;
+        let announcement = self
+            .contents
+            .entry(content)
+            .or_default()
+            .entry(peer)
+            .or_insert_with(|| Announcement {
+                chunks: BTreeSet::new(),
+                expires_at,
+            });
+        announcement.expires_at = expires_at;
+
+        let mut ignored = 0;
+        for chunk in chunks {
+            if announcement.chunks.contains(&chunk) {
+                continue;
+            }
+            if room == 0 {
+                ignored += 1;
+                continue;
+            }
+            announcement.chunks.insert(chunk);
+            room -= 1;
+        }
+        if ignored > 0 {
+            println!(
+                "Ignoring {} chunks from {}, which announced more than {}",
+                ignored, peer, MAX_CHUNKS_PER_PEER
+            );
+        }
+    }
+
+    /// Forgets everything `peer` announced.
+    pub fn remove_peer(&mut self, peer: &SocketAddr) {
+        for providers in self.contents.values_mut() {
+            providers.remove(peer);
+        }
+        self.contents
+            .retain(|_content, providers| !providers.is_empty());
+    }
+
+    /// Drops the announcements that were not renewed in time.
+    pub fn expire(&mut self) {
+        let now = Instant::now();
+        for providers in self.contents.values_mut() {
+            providers.retain(|peer, announcement| {
+                let alive = announcement.expires_at > now;
+                if !alive {
+                    println!("Announcement from {} expired", peer);
+                }
+                alive
+            });
+        }
+        self.contents
+            .retain(|_content, providers| !providers.is_empty());
+    }
+
+    /// Every peer holding some of `chunks` of `content`, along with the ones it holds.
+    pub fn providers(
+        &self,
+        content: &ContentId,
+        chunks: &[ChunkId],
+    ) -> Vec<(SocketAddr, Vec<ChunkId>)> {
+        let providers = match self.contents.get(content) {
+            Some(providers) => providers,
+            None => return Vec::new(),
+        };
+
+        providers
+            .iter()
+            .map(|(peer, announcement)| {
+                let held = chunks
+                    .iter()
+                    .filter(|chunk| announcement.chunks.contains(chunk))
+                    .copied()
+                    .collect::<Vec<ChunkId>>();
+                (*peer, held)
+            })
+            .filter(|(_peer, held)| !held.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn video() -> ContentId {
+        ContentId::new("sintel").unwrap()
+    }
+
+    fn sorted(mut providers: Vec<(SocketAddr, Vec<ChunkId>)>) -> Vec<(SocketAddr, Vec<ChunkId>)> {
+        providers.sort();
+        providers
+    }
+
+    #[test]
+    fn providers_hold_some_of_the_chunks_asked_for() {
+        let mut registry = Registry::new(Duration::from_secs(60));
+        registry.announce(peer(1), video(), vec![3, 1, 2, 2]);
+        registry.announce(peer(2), video(), vec![5]);
+        registry.announce(peer(3), ContentId::default(), vec![1]);
+
+        assert_eq!(
+            sorted(registry.providers(&video(), &[1, 3, 4, 5])),
+            vec![(peer(1), vec![1, 3]), (peer(2), vec![5])]
+        );
+        assert!(registry.providers(&video(), &[4]).is_empty());
+        assert!(registry
+            .providers(&ContentId::new("other").unwrap(), &[1])
+            .is_empty());
+    }
+
+    #[test]
+    fn announce_replaces_the_previous_announcement() {
+        let mut registry = Registry::new(Duration::from_secs(60));
+        registry.announce(peer(1), video(), vec![1, 2]);
+        registry.announce(peer(1), video(), vec![3]);
+
+        assert_eq!(
+            registry.providers(&video(), &[1, 2, 3]),
+            vec![(peer(1), vec![3])]
+        );
+    }
+
+    #[test]
+    fn announce_more_adds_to_the_previous_announcement() {
+        let mut registry = Registry::new(Duration::from_secs(60));
+        registry.announce(peer(1), video(), vec![1, 2]);
+        registry.announce_more(peer(1), video(), vec![4, 2]);
+        registry.announce_more(peer(2), video(), vec![3]);
+
+        assert_eq!(
+            sorted(registry.providers(&video(), &[1, 2, 3, 4])),
+            vec![(peer(1), vec![1, 2, 4]), (peer(2), vec![3])]
+        );
+    }
+
+    #[test]
+    fn empty_announce_withdraws_the_peer() {
+        let mut registry = Registry::new(Duration::from_secs(60));
+        registry.announce(peer(1), video(), vec![1]);
+        registry.announce(peer(2), video(), vec![1]);
+
+        registry.announce(peer(1), video(), Vec::new());
+
+        assert_eq!(registry.providers(&video(), &[1]), vec![(peer(2), vec![1])]);
+    }
+
+    #[test]
+    fn removed_peer_is_forgotten_for_every_content() {
+        let mut registry = Registry::new(Duration::from_secs(60));
+        registry.announce(peer(1), video(), vec![1]);
+        registry.announce(peer(1), ContentId::default(), vec![1]);
+
+        registry.remove_peer(&peer(1));
+
+        assert!(registry.providers(&video(), &[1]).is_empty());
+        assert!(registry.contents.is_empty());
+    }
+
+    #[test]
+    fn announcements_expire_unless_renewed() {
+        let mut registry = Registry::new(Duration::from_millis(50));
+        registry.announce(peer(1), video(), vec![1]);
+        registry.announce(peer(2), video(), vec![1]);
+        thread::sleep(Duration::from_millis(30));
+        registry.announce_more(peer(2), video(), vec![2]);
+        thread::sleep(Duration::from_millis(30));
+
+        registry.expire();
+
+        assert_eq!(
+            registry.providers(&video(), &[1, 2]),
+            vec![(peer(2), vec![1, 2])]
+        );
+    }
+
+    fn held(registry: &Registry, content: &ContentId, chunks: &[ChunkId]) -> usize {
+        registry
+            .providers(content, chunks)
+            .iter()
+            .map(|(_peer, held)| held.len())
+            .sum()
+    }
+
+    #[test]
+    fn chunks_beyond_the_cap_per_peer_are_ignored() {
+        let mut registry = Registry::new(Duration::from_secs(60));
+        let other = ContentId::new("other").unwrap();
+        let half = (MAX_CHUNKS_PER_PEER / 2) as ChunkId;
+        registry.announce(peer(1), video(), (0..half).collect());
+        registry.announce(peer(1), other.clone(), (0..half + 10).collect());
+        registry.announce_more(peer(1), video(), vec![0, half]);
+
+        assert_eq!(held(&registry, &video(), &[0, half]), 1);
+        assert_eq!(held(&registry, &other, &[half - 1, half]), 1);
+
+        // Withdrawing a content makes room again.
+        registry.announce(peer(1), other, Vec::new());
+        registry.announce_more(peer(1), video(), vec![half]);
+        assert_eq!(held(&registry, &video(), &[0, half]), 2);
+    }
+}
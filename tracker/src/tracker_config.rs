@@ -0,0 +1,48 @@
+use std::{env, net::SocketAddr, str::FromStr, time::Duration};
+
+#[derive(Debug)]
+pub struct TrackerConfig {
+    pub address: SocketAddr,
+    /// How long an announcement is kept. Peers announce again well before it runs out, so
+    /// only departed peers expire.
+    pub expiry: Duration,
+}
+
+impl TrackerConfig {
+    /// Parses `<address>`, optionally followed by `--expiry <seconds>`.
+    pub fn new(mut args: env::Args) -> Result<TrackerConfig, String> {
+        args.next();
+
+        let mut address = None;
+        let mut expiry_secs: u64 = 90;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--expiry" => expiry_secs = parse_value(&mut args, "expiry")?,
+                _ if address.is_none() => {
+                    address = Some(
+                        arg.parse()
+                            .map_err(|_| format!("Unable to parse IP {}", arg))?,
+                    )
+                }
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+
+        Ok(TrackerConfig {
+            address: address.ok_or("Address not specified")?,
+            expiry: Duration::from_secs(expiry_secs.max(1)),
+        })
+    }
+}
+
+/// Parses the value following an option.
+fn parse_value<T: FromStr>(args: &mut env::Args, name: &str) -> Result<T, String> {
+    let value = args
+        .next()
+        .ok_or_else(|| format!("Value of {} not specified", name))?;
+
+    value
+        .parse()
+        .map_err(|_| format!("Unable to parse {} '{}'", name, value))
+}